@@ -1,9 +1,11 @@
+use std::future::Future;
+use std::task::{Context, Waker};
 use std::{collections::btree_map::Entry, path::Path, sync::Arc};
 
 use arc_swap::{access::Map, ArcSwap};
-use futures_util::FutureExt;
 use helix_core::diagnostic::Severity;
 use helix_core::{pos_at_coords, syntax, Position, Selection};
+use helix_event::AsyncHook;
 
 use helix_lsp::{
     lsp::{self, notification::Notification},
@@ -20,14 +22,24 @@ use helix_view::{doc_mut, graphics::Rect, handlers::Handlers, theme, Editor};
 use anyhow::Error;
 use log::{debug, error, info, warn};
 use serde_json::json;
+use tokio::sync::mpsc::error::TrySendError;
 use tokio_stream::StreamExt;
 
+use crate::completion::CompletionHandler;
+use crate::signature_help::SignatureHelpHandler;
+
 pub struct Application {
     pub editor: Editor,
     pub compositor: Compositor,
     pub view: EditorView,
     pub jobs: Jobs,
     pub lsp_progress: LspProgressMap,
+    /// files shown next to the native picker
+    pub picker_previews: crate::picker::Previews,
+    redraw_requests: tokio::sync::mpsc::Receiver<()>,
+    step_waker: Option<Waker>,
+    // the last overlay had layers drawn through the TUI renderer
+    shows_layers: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -38,46 +50,53 @@ pub enum InputEvent {
         direction: helix_core::movement::Direction,
         view_id: helix_view::ViewId,
     },
+    ScrollColumns {
+        column_count: usize,
+        direction: helix_core::movement::Direction,
+        view_id: helix_view::ViewId,
+    },
+    Hover {
+        view_id: helix_view::ViewId,
+        char_idx: usize,
+    },
+    /// Left click on the cell at `row` and `col` of the view, gutter included.
+    Click {
+        view_id: helix_view::ViewId,
+        row: u16,
+        col: u16,
+        click_count: usize,
+        add_cursor: bool,
+    },
+    /// Mouse moved with the left button held after a click, rows above
+    /// or below the view select into the lines scrolled out of it.
+    Drag {
+        view_id: helix_view::ViewId,
+        row: i32,
+        col: u16,
+    },
+    /// Text committed by an input method, inserted at every cursor of the view.
+    Text {
+        view_id: helix_view::ViewId,
+        text: String,
+    },
 }
 
 pub struct Input;
 
 impl gpui::EventEmitter<InputEvent> for Input {}
 
-pub struct Crank;
-
-impl gpui::EventEmitter<()> for Crank {}
-
 impl Application {
-    fn emit_overlays(&mut self, cx: &mut gpui::ModelContext<'_, crate::Core>) {
-        use crate::picker::Picker as PickerComponent;
-        use crate::prompt::Prompt;
-        use helix_term::ui::{overlay::Overlay, Picker};
-        use std::path::PathBuf;
-
-        let picker = if let Some(p) = self
-            .compositor
-            .find_id::<Overlay<Picker<PathBuf>>>(helix_term::ui::picker::ID)
-        {
-            println!("found file picker");
-            Some(PickerComponent::make(&mut self.editor, &mut p.content))
-        } else {
-            None
-        };
-        let prompt = if let Some(p) = self.compositor.find::<helix_term::ui::Prompt>() {
-            Some(Prompt::make(&mut self.editor, p))
-        } else {
-            None
-        };
-
-        if let Some(picker) = picker {
-            cx.emit(crate::Update::Picker(picker));
-        }
-
-        if let Some(prompt) = prompt {
-            cx.emit(crate::Update::Prompt(prompt));
-        }
+    /// Renders the compositor layers again, for after something that could have
+    /// pushed, removed or changed them, and announces the pending info box.
+    pub fn emit_overlays(&mut self, cx: &mut gpui::ModelContext<'_, crate::Core>) {
+        let layers =
+            crate::overlay::Layers::make(&mut self.editor, &mut self.compositor, &mut self.jobs);
+        self.shows_layers = layers.is_some();
+        cx.emit(crate::Update::Overlay(layers));
+        self.emit_info(cx);
+    }
 
+    fn emit_info(&mut self, cx: &mut gpui::ModelContext<'_, crate::Core>) {
         if let Some(info) = self.editor.autoinfo.take() {
             cx.emit(crate::Update::Info(info));
         }
@@ -100,20 +119,33 @@ impl Application {
         };
         match event {
             InputEvent::Key(key) => {
+                if let Some(zoom) = self.gui_command(key) {
+                    self.emit_overlays(cx);
+                    cx.emit(crate::Update::TextZoom(zoom));
+                    return;
+                }
                 let mut is_handled = self
                     .compositor
                     .handle_event(&helix_view::input::Event::Key(key), &mut comp_ctx);
+                // layers only change when they get the key or a command calls back
+                // into the compositor, plain editing leaves them alone
+                let mut layers_changed = is_handled || self.shows_layers;
                 if !is_handled {
                     let event = &helix_view::input::Event::Key(key);
                     let res = self.view.handle_event(event, &mut comp_ctx);
                     is_handled = matches!(res, EventResult::Consumed(_));
                     if let EventResult::Consumed(Some(cb)) = res {
                         cb(&mut self.compositor, &mut comp_ctx);
+                        layers_changed = true;
                     }
                 }
                 let _is_handled = is_handled;
                 // println!("KEY IS HANDLED ? {:?}", is_handled);
-                self.emit_overlays(cx);
+                if layers_changed {
+                    self.emit_overlays(cx);
+                } else {
+                    self.emit_info(cx);
+                }
                 cx.emit(crate::Update::Redraw);
             }
             InputEvent::ScrollLines {
@@ -132,7 +164,252 @@ impl Application {
                 helix_term::commands::scroll(&mut ctx, line_count, direction, false);
                 cx.emit(crate::Update::Redraw);
             }
+            InputEvent::ScrollColumns {
+                column_count,
+                direction,
+                view_id,
+            } => {
+                self.scroll_columns(view_id, column_count, direction);
+                cx.emit(crate::Update::Redraw);
+            }
+            InputEvent::Hover { view_id, char_idx } => {
+                crate::hover::request_hover(&mut self.editor, view_id, char_idx);
+            }
+            InputEvent::Click {
+                view_id,
+                row,
+                col,
+                click_count,
+                add_cursor,
+            } => {
+                self.click(view_id, row, col, click_count, add_cursor);
+                if self.shows_layers {
+                    self.emit_overlays(cx);
+                }
+                cx.emit(crate::Update::Redraw);
+            }
+            InputEvent::Drag { view_id, row, col } => {
+                self.drag(view_id, row, col);
+                cx.emit(crate::Update::Redraw);
+            }
+            InputEvent::Text { view_id, text } => {
+                use helix_view::document::Mode;
+
+                // outside of insert mode the text is typed as keys, so that
+                // commands taking a char like `f` or `r` get composed ones
+                if self.editor.mode() != Mode::Insert || !self.editor.tree.contains(view_id) {
+                    for c in text.chars() {
+                        let key = helix_view::input::KeyEvent {
+                            code: helix_view::keyboard::KeyCode::Char(c),
+                            modifiers: helix_view::keyboard::KeyModifiers::NONE,
+                        };
+                        self.handle_input_event(InputEvent::Key(key), cx, handle.clone());
+                    }
+                    return;
+                }
+                self.insert_text(view_id, &text);
+                if self.shows_layers {
+                    self.emit_overlays(cx);
+                }
+                cx.emit(crate::Update::Redraw);
+            }
+        }
+        // commands may have queued new jobs, make sure they get polled
+        self.wake_step();
+    }
+
+    // helix has no command for it, the cursor is left where it is and
+    // the next command brings it back into view
+    fn scroll_columns(
+        &mut self,
+        view_id: helix_view::ViewId,
+        column_count: usize,
+        direction: helix_core::movement::Direction,
+    ) {
+        use helix_core::movement::Direction;
+
+        if !self.editor.tree.contains(view_id) {
+            return;
+        }
+        let view = self.editor.tree.get_mut(view_id);
+        let doc = &self.editor.documents[&view.doc];
+        let text_format = doc.text_format(view.inner_width(doc), None);
+        if text_format.soft_wrap {
+            return;
+        }
+
+        // don't scroll past the longest line in view, measured in columns
+        // so that tabs and wide chars count as drawn
+        let annotations = view.text_annotations(doc, None);
+        let text = doc.text().slice(..);
+        let first_line = text.char_to_line(view.offset.anchor.min(text.len_chars()));
+        let last_line = (first_line + view.inner_height()).min(text.len_lines());
+        let max_offset = (first_line..last_line)
+            .map(|line| {
+                let line_start = text.line_to_char(line);
+                let line_end = helix_core::line_ending::line_end_char_index(&text, line);
+                let (pos, _) = helix_core::visual_offset_from_block(
+                    text,
+                    line_start,
+                    line_end,
+                    &text_format,
+                    &annotations,
+                );
+                pos.col
+            })
+            .max()
+            .unwrap_or(0)
+            .saturating_sub(1);
+        drop(annotations);
+
+        let offset = &mut view.offset.horizontal_offset;
+        *offset = match direction {
+            Direction::Forward => (*offset + column_count).min(max_offset.max(*offset)),
+            Direction::Backward => offset.saturating_sub(column_count),
+        };
+    }
+
+    // helix's typable commands can't be extended, the GUI's own are
+    // taken out of the `:` prompt when it is submitted
+    fn gui_command(&mut self, key: helix_view::input::KeyEvent) -> Option<crate::TextZoom> {
+        use crate::TextZoom;
+        use helix_term::compositor::AnyComponent;
+
+        if key != helix_view::key!(Enter) {
+            return None;
+        }
+        // Enter goes to the top layer, which may be a popup over the prompt
+        let top = self.compositor.pop()?;
+        let line = top
+            .as_any()
+            .downcast_ref::<crate::commands::CommandPrompt>()
+            .map(|prompt| prompt.line().to_string());
+        let zoom = line.as_deref().and_then(|line| match line.trim() {
+            "zoom-in" => Some(TextZoom::In),
+            "zoom-out" => Some(TextZoom::Out),
+            "zoom-reset" => Some(TextZoom::Reset),
+            _ => None,
+        });
+        let (Some(line), Some(zoom)) = (line, zoom) else {
+            self.compositor.push(top);
+            return None;
+        };
+        // remembered like the commands helix runs
+        if let Err(err) = self.editor.registers.push(':', line) {
+            self.editor.set_error(err.to_string());
+        }
+        Some(zoom)
+    }
+
+    // typed like keys in insert mode, so that auto pairs and completion
+    // triggers work, with one history point so that undo takes back the
+    // whole composed text
+    fn insert_text(&mut self, view_id: helix_view::ViewId, text: &str) {
+        self.editor.focus(view_id);
+        let doc = doc_mut!(self.editor, &self.editor.tree.get(view_id).doc);
+        let cursors = doc.selection(view_id).clone().cursors(doc.text().slice(..));
+        doc.set_selection(view_id, cursors);
+
+        let mut cx = helix_term::commands::Context {
+            register: None,
+            count: None,
+            editor: &mut self.editor,
+            callback: Vec::new(),
+            on_next_key_callback: None,
+            jobs: &mut self.jobs,
+        };
+        for c in text.chars() {
+            helix_term::commands::insert::insert_char(&mut cx, c);
+        }
+
+        let view = self.editor.tree.get_mut(view_id);
+        let doc = doc_mut!(self.editor, &view.doc);
+        doc.append_changes_to_history(view);
+        self.editor.ensure_cursor_in_view(view_id);
+    }
+
+    // char at a cell of the view, cells in the gutter are moved to the first column of text
+    fn char_at_cell(&self, view_id: helix_view::ViewId, row: u16, col: u16) -> Option<usize> {
+        let view = self.editor.tree.try_get(view_id)?;
+        let doc = self.editor.document(view.doc)?;
+        let inner = view.inner_area(doc);
+        let column = (view.area.x + col).clamp(inner.x, inner.right().saturating_sub(1));
+        view.pos_at_screen_coords(doc, view.area.y + row, column, true)
+    }
+
+    // mostly copy pasted from helix_term/src/ui/editor.rs, with double and
+    // triple clicks selecting the word and the line
+    fn click(
+        &mut self,
+        view_id: helix_view::ViewId,
+        row: u16,
+        col: u16,
+        click_count: usize,
+        add_cursor: bool,
+    ) {
+        use helix_core::textobject::{textobject_word, TextObject};
+        use helix_core::Range;
+        use helix_view::document::Mode;
+
+        let Some(pos) = self.char_at_cell(view_id, row, col) else {
+            return;
+        };
+        let mode = self.editor.mode;
+        let doc = doc_mut!(self.editor, &self.editor.tree.get(view_id).doc);
+        let text = doc.text().slice(..);
+        let range = match click_count {
+            2 => textobject_word(text, Range::point(pos), TextObject::Inside, 1, false),
+            3 => {
+                let line = text.char_to_line(pos);
+                Range::new(text.line_to_char(line), text.line_to_char(line + 1))
+            }
+            _ if mode == Mode::Select && !add_cursor => {
+                doc.selection(view_id).primary().put_cursor(text, pos, true)
+            }
+            _ => Range::point(pos),
+        };
+        let selection = if add_cursor {
+            doc.selection(view_id).clone().push(range)
+        } else {
+            Selection::single(range.anchor, range.head)
+        };
+        doc.set_selection(view_id, selection);
+
+        self.editor.focus(view_id);
+        self.editor.ensure_cursor_in_view(view_id);
+    }
+
+    // extends the primary selection to the dragged to char, a row beyond
+    // the view selects into the next line out of it, which scrolls it in
+    fn drag(&mut self, view_id: helix_view::ViewId, row: i32, col: u16) {
+        let Some(view) = self.editor.tree.try_get(view_id) else {
+            return;
+        };
+        let height = view.inner_height() as i32;
+        let visible_row = row.clamp(0, (height - 1).max(0));
+        let Some(mut pos) = self.char_at_cell(view_id, visible_row as u16, col) else {
+            return;
+        };
+
+        let doc = doc_mut!(self.editor, &self.editor.tree.get(view_id).doc);
+        let text = doc.text().slice(..);
+        if row != visible_row {
+            let line = text.char_to_line(pos);
+            let column = pos - text.line_to_char(line);
+            let target = if row < visible_row {
+                line.saturating_sub(1)
+            } else {
+                (line + 1).min(text.len_lines() - 1)
+            };
+            let line_end = helix_core::line_ending::line_end_char_index(&text, target);
+            pos = (text.line_to_char(target) + column).min(line_end);
         }
+
+        let mut selection = doc.selection(view_id).clone();
+        let primary = selection.primary_mut();
+        *primary = primary.put_cursor(text, pos, true);
+        doc.set_selection(view_id, selection);
+        self.editor.ensure_cursor_in_view(view_id);
     }
 
     fn handle_document_write(&mut self, doc_save_event: &DocumentSavedEventResult) {
@@ -178,24 +455,32 @@ impl Application {
         ));
     }
 
-    pub fn handle_crank_event(
+    /// Polls the editor event sources once, handling everything that is ready.
+    /// Wakers of the pending sources are registered with `task_cx`, so the
+    /// caller is polled again as soon as something arrives.
+    ///
+    /// The step future borrows the application and the model context, so it can't
+    /// outlive this call and a new one is made on every wake. That only drops the
+    /// source futures waiting in `select!`, which are all cancel safe; events are
+    /// handled without awaiting, whatever has to wait is spawned on the runtime.
+    pub fn poll_step(
         &mut self,
-        _event: (),
+        task_cx: &mut Context<'_>,
         cx: &mut gpui::ModelContext<'_, crate::Core>,
-        handle: tokio::runtime::Handle,
+        handle: &tokio::runtime::Handle,
     ) {
         let _guard = handle.enter();
 
-        self.step(cx).now_or_never();
-        /*
-        use std::future::Future;
-        let fut = self.step(cx);
-        let mut fut = Box::pin(fut);
-        handle.block_on(std::future::poll_fn(move |cx| {
-            let _ = fut.as_mut().poll(cx);
-            Poll::Ready(())
-        }));
-        */
+        self.step_waker = Some(task_cx.waker().clone());
+        let step = std::pin::pin!(self.step(cx));
+        let _ = step.poll(task_cx);
+    }
+
+    // sources like `jobs.wait_futures` don't register a waker while they are empty
+    fn wake_step(&self) {
+        if let Some(waker) = &self.step_waker {
+            waker.wake_by_ref();
+        }
     }
 
     pub async fn step(&mut self, cx: &mut gpui::ModelContext<'_, crate::Core>) {
@@ -209,7 +494,8 @@ impl Application {
                 // }
                 Some(callback) = self.jobs.callbacks.recv() => {
                     self.jobs.handle_callback(&mut self.editor, &mut self.compositor, Ok(Some(callback)));
-                    // self.render().await;
+                    self.emit_overlays(cx);
+                    cx.emit(crate::Update::Redraw);
                 }
                 Some(msg) = self.jobs.status_messages.recv() => {
                     let severity = match msg.severity{
@@ -226,7 +512,13 @@ impl Application {
                 }
                 Some(callback) = self.jobs.wait_futures.next() => {
                     self.jobs.handle_callback(&mut self.editor, &mut self.compositor, callback);
-                    // self.render().await;
+                    self.emit_overlays(cx);
+                    cx.emit(crate::Update::Redraw);
+                }
+                Some(()) = self.redraw_requests.recv() => {
+                    // pickers request a redraw when new matches arrive
+                    self.emit_overlays(cx);
+                    cx.emit(crate::Update::Redraw);
                 }
                 event = self.editor.wait_event() => {
                     use helix_view::editor::EditorEvent;
@@ -237,16 +529,22 @@ impl Application {
                         }
                         EditorEvent::IdleTimer => {
                             self.editor.clear_idle_timer();
-                            /* dont send */
+                            // what EditorView does on idle timeout, the responses
+                            // come back as job callbacks and trigger a redraw
+                            helix_term::commands::compute_inlay_hints_for_all_views(
+                                &mut self.editor,
+                                &mut self.jobs,
+                            );
                         }
                         EditorEvent::Redraw => {
-                             cx.emit(crate::Update::EditorEvent(EditorEvent::Redraw));
+                            self.editor.needs_redraw = false;
+                            cx.emit(crate::Update::EditorEvent(EditorEvent::Redraw));
                         }
                         EditorEvent::ConfigEvent(_) => {
                             /* TODO */
                         }
                         EditorEvent::LanguageServerMessage((id, call)) => {
-                            self.handle_language_server_message(call, id).await;
+                            self.handle_language_server_message(call, id);
                         }
                         EditorEvent::DebuggerEvent(_) => {
                             /* TODO */
@@ -261,7 +559,7 @@ impl Application {
     }
 
     // copy pasted from helix_term/src/application.rs
-    async fn handle_language_server_message(
+    fn handle_language_server_message(
         &mut self,
         call: helix_lsp::Call,
         server_id: LanguageServerId,
@@ -616,7 +914,14 @@ impl Application {
                         }
                     }
                     Ok(MethodCall::WorkspaceFolders) => {
-                        Ok(json!(&*language_server!().workspace_folders().await))
+                        // the folders are behind an async lock, waiting for it in here
+                        // would be cancelled when the step is polled again
+                        let language_server = language_server!().clone();
+                        tokio::spawn(async move {
+                            let folders = json!(&*language_server.workspace_folders().await);
+                            language_server.reply(id, Ok(folders)).await
+                        });
+                        return;
                     }
                     Ok(MethodCall::WorkspaceConfiguration(params)) => {
                         let language_server = language_server!();
@@ -711,7 +1016,7 @@ impl Application {
 
 pub fn init_editor(
     args: Args,
-    config: Config,
+    mut config: Config,
     lang_loader: syntax::Loader,
 ) -> Result<Application, Error> {
     use helix_view::editor::Action;
@@ -736,6 +1041,8 @@ pub fn init_editor(
         })
         .unwrap_or_else(|| theme_loader.default_theme(true_color));
 
+    crate::commands::replace_commands(&mut config.keys);
+
     let syn_loader = Arc::new(ArcSwap::from_pointee(lang_loader));
     let config = Arc::new(ArcSwap::from_pointee(config));
 
@@ -745,11 +1052,9 @@ pub fn init_editor(
         width: 80,
         height: 25,
     };
-    let (tx, _rx) = tokio::sync::mpsc::channel(1);
-    let (tx1, _rx1) = tokio::sync::mpsc::channel(1);
     let handlers = Handlers {
-        completions: tx,
-        signature_hints: tx1,
+        completions: CompletionHandler::new(config.clone()).spawn(),
+        signature_hints: SignatureHelpHandler::default().spawn(),
     };
     let mut editor = Editor::new(
         area,
@@ -761,6 +1066,8 @@ pub fn init_editor(
         handlers,
     );
 
+    let mut compositor = Compositor::new(area);
+
     if args.load_tutor {
         let path = helix_loader::runtime_file(Path::new("tutor"));
         // let path = Path::new("./test.rs");
@@ -776,6 +1083,8 @@ pub fn init_editor(
 
         // Unset path to prevent accidentally saving to the original tutor file.
         doc_mut!(editor).set_path(None);
+    } else if !args.files.is_empty() {
+        open_files(&mut editor, &mut compositor, &args, &config.load())?;
     } else {
         editor.new_file(Action::VerticalSplit);
     }
@@ -785,17 +1094,25 @@ pub fn init_editor(
     let keys = Box::new(Map::new(Arc::clone(&config), |config: &Config| {
         &config.keys
     }));
-    let compositor = Compositor::new(Rect {
-        x: 0,
-        y: 0,
-        width: 80,
-        height: 25,
-    });
     let keymaps = Keymaps::new(keys);
     let view = EditorView::new(keymaps);
     let jobs = Jobs::new();
 
     helix_term::events::register();
+    crate::completion::register_hooks(&editor.handlers);
+    crate::signature_help::register_hooks(&editor.handlers, config.clone());
+
+    // `Editor::wait_event` only sees redraw requests while it is being polled,
+    // forward them so that the gui gets woken up
+    let (redraw_tx, redraw_requests) = tokio::sync::mpsc::channel(1);
+    tokio::spawn(async move {
+        loop {
+            helix_event::redraw_requested().await;
+            if let Err(TrySendError::Closed(_)) = redraw_tx.try_send(()) {
+                break;
+            }
+        }
+    });
 
     Ok(Application {
         editor,
@@ -803,5 +1120,74 @@ pub fn init_editor(
         view,
         jobs,
         lsp_progress: LspProgressMap::new(),
+        picker_previews: Default::default(),
+        redraw_requests,
+        shows_layers: false,
+        step_waker: None,
     })
 }
+
+// mostly copy pasted from helix_term/src/application.rs
+fn open_files(
+    editor: &mut Editor,
+    compositor: &mut Compositor,
+    args: &Args,
+    config: &Config,
+) -> Result<(), Error> {
+    use helix_view::editor::Action;
+    use helix_view::tree::Layout;
+    use helix_view::{align_view, current, Align};
+
+    let mut files_it = args.files.iter().cloned().peekable();
+
+    // If the first file is a directory, skip it and open a picker
+    if let Some((first, _)) = files_it.next_if(|(p, _)| p.is_dir()) {
+        let picker = crate::picker::file_picker(first, &config.editor);
+        compositor.push(Box::new(picker));
+    }
+
+    // If there are no more files specified, start with a scratch buffer
+    if files_it.peek().is_none() {
+        editor.new_file(Action::VerticalSplit);
+        return Ok(());
+    }
+
+    let mut nr_of_files = 0;
+    for (file, pos) in files_it {
+        nr_of_files += 1;
+        if file.is_dir() {
+            return Err(anyhow::anyhow!(
+                "expected a path to file, found a directory. (to open a directory pass it as first argument)"
+            ));
+        }
+        let action = match args.split {
+            _ if nr_of_files == 1 => Action::VerticalSplit,
+            Some(Layout::Vertical) => Action::VerticalSplit,
+            Some(Layout::Horizontal) => Action::HorizontalSplit,
+            None => Action::Load,
+        };
+        let doc_id = editor.open(&file, action)?;
+        // We can't open more than 1 buffer for 1 file, in this case we already have opened this file previously
+        if nr_of_files != editor.documents.len() {
+            nr_of_files -= 1;
+        }
+        // with Action::Load all documents have the same view, with
+        // --vsplit or --hsplit the file which is opened last is focused on
+        let view_id = editor.tree.focus;
+        let doc = doc_mut!(editor, &doc_id);
+        let pos = Selection::point(pos_at_coords(doc.text().slice(..), pos, true));
+        doc.set_selection(view_id, pos);
+    }
+
+    editor.set_status(format!(
+        "Loaded {} file{}.",
+        nr_of_files,
+        if nr_of_files == 1 { "" } else { "s" } // avoid "Loaded 1 files." grammo
+    ));
+    // align the view to center after all files are loaded,
+    // does not affect views without pos since it is at the top
+    let (view, doc) = current!(editor);
+    align_view(doc, view, Align::Center);
+
+    Ok(())
+}
@@ -0,0 +1,772 @@
+use std::collections::HashSet;
+
+use gpui::prelude::FluentBuilder;
+use gpui::*;
+use helix_core::syntax::LanguageServerFeature;
+use helix_core::Rope;
+use helix_lsp::{lsp, util, LanguageServerId, OffsetEncoding};
+use helix_stdx::path::get_relative_path;
+use helix_term::commands;
+use helix_term::compositor::{self, Component, Compositor, EventResult};
+use helix_term::job::dispatch;
+use helix_view::input::Event;
+use helix_view::{ctrl, current_ref, key, shift, Editor, ViewId};
+
+use crate::utils::color_to_hsla;
+
+/// lines of the edit preview, longer previews are truncated
+const MAX_PREVIEW_LINES: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Group {
+    QuickFix,
+    Refactor,
+    Source,
+    Other,
+}
+
+impl Group {
+    fn of(kind: Option<&lsp::CodeActionKind>) -> Self {
+        let Some(kind) = kind else {
+            return Group::Other;
+        };
+        let kind = kind.as_str();
+        if kind.starts_with(lsp::CodeActionKind::QUICKFIX.as_str()) {
+            Group::QuickFix
+        } else if kind.starts_with(lsp::CodeActionKind::REFACTOR.as_str()) {
+            Group::Refactor
+        } else if kind.starts_with(lsp::CodeActionKind::SOURCE.as_str()) {
+            Group::Source
+        } else {
+            Group::Other
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Group::QuickFix => "Quick Fix",
+            Group::Refactor => "Refactor",
+            Group::Source => "Source",
+            Group::Other => "Other",
+        }
+    }
+}
+
+struct CodeActionItem {
+    action: lsp::CodeActionOrCommand,
+    language_server_id: LanguageServerId,
+    resolved: bool,
+}
+
+impl CodeActionItem {
+    fn title(&self) -> &str {
+        match &self.action {
+            lsp::CodeActionOrCommand::Command(command) => &command.title,
+            lsp::CodeActionOrCommand::CodeAction(action) => &action.title,
+        }
+    }
+
+    fn group(&self) -> Group {
+        match &self.action {
+            lsp::CodeActionOrCommand::Command(_) => Group::Other,
+            lsp::CodeActionOrCommand::CodeAction(action) => Group::of(action.kind.as_ref()),
+        }
+    }
+
+    fn is_preferred(&self) -> bool {
+        match &self.action {
+            lsp::CodeActionOrCommand::Command(_) => false,
+            lsp::CodeActionOrCommand::CodeAction(action) => action.is_preferred.unwrap_or(false),
+        }
+    }
+
+    fn edit(&self) -> Option<&lsp::WorkspaceEdit> {
+        match &self.action {
+            lsp::CodeActionOrCommand::Command(_) => None,
+            lsp::CodeActionOrCommand::CodeAction(action) => action.edit.as_ref(),
+        }
+    }
+}
+
+// mostly copy pasted from helix_term/src/commands/lsp.rs
+fn request_code_actions(editor: &mut Editor) {
+    let (view, doc) = current_ref!(editor);
+    let view_id = view.id;
+    let selection_range = doc.selection(view.id).primary();
+    let anchor = selection_range.cursor(doc.text().slice(..));
+    let diagnostics = doc.path().and_then(|path| editor.diagnostics.get(path));
+
+    let mut seen_language_servers = HashSet::new();
+    let futures: Vec<_> = doc
+        .language_servers_with_feature(LanguageServerFeature::CodeAction)
+        .filter(|ls| seen_language_servers.insert(ls.id()))
+        .filter_map(|language_server| {
+            let offset_encoding = language_server.offset_encoding();
+            let language_server_id = language_server.id();
+            let range = util::range_to_lsp_range(doc.text(), selection_range, offset_encoding);
+            let diagnostics = diagnostics
+                .into_iter()
+                .flatten()
+                .filter(|(diag, id)| {
+                    *id == language_server_id
+                        && diag.range.start <= range.end
+                        && range.start <= diag.range.end
+                })
+                .map(|(diag, _)| diag.clone())
+                .collect();
+            let context = lsp::CodeActionContext {
+                diagnostics,
+                only: None,
+                trigger_kind: Some(lsp::CodeActionTriggerKind::INVOKED),
+            };
+            let future = language_server.code_actions(doc.identifier(), range, context)?;
+            Some(async move {
+                let json = future.await?;
+                let response: Option<lsp::CodeActionResponse> = serde_json::from_value(json)?;
+                let items = response
+                    .unwrap_or_default()
+                    .into_iter()
+                    // remove disabled code actions
+                    .filter(|action| {
+                        !matches!(
+                            action,
+                            lsp::CodeActionOrCommand::CodeAction(lsp::CodeAction {
+                                disabled: Some(_),
+                                ..
+                            })
+                        )
+                    })
+                    .map(|action| CodeActionItem {
+                        action,
+                        language_server_id,
+                        resolved: false,
+                    })
+                    .collect::<Vec<_>>();
+                anyhow::Ok(items)
+            })
+        })
+        .collect();
+
+    if futures.is_empty() {
+        editor.set_error("No configured language server supports code actions");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut items = Vec::new();
+        for response in futures_util::future::join_all(futures).await {
+            match response {
+                Ok(actions) => items.extend(actions),
+                Err(err) => log::error!("code action request failed: {err}"),
+            }
+        }
+        dispatch(move |editor, compositor| {
+            show_code_actions(editor, compositor, view_id, anchor, items)
+        })
+        .await
+    });
+}
+
+fn show_code_actions(
+    editor: &mut Editor,
+    compositor: &mut Compositor,
+    view_id: ViewId,
+    anchor: usize,
+    mut items: Vec<CodeActionItem>,
+) {
+    if items.is_empty() {
+        editor.set_status("No code actions available");
+        compositor.remove(CodeActions::ID);
+        return;
+    }
+    // groups in a fixed order, preferred actions first within a group
+    items.sort_by_key(|item| (item.group(), !item.is_preferred()));
+
+    let mut code_actions = CodeActions {
+        items,
+        cursor: 0,
+        view_id,
+        anchor,
+        preview: None,
+    };
+    code_actions.resolve_selected(editor);
+    compositor.replace_or_push(CodeActions::ID, code_actions);
+}
+
+// mostly copy pasted from helix_term/src/commands/lsp.rs
+fn execute_lsp_command(
+    editor: &mut Editor,
+    language_server_id: LanguageServerId,
+    cmd: lsp::Command,
+) {
+    // the command is executed on the server and communicated back
+    // to the client asynchronously using workspace edits
+    let future = match editor
+        .language_server_by_id(language_server_id)
+        .and_then(|language_server| language_server.command(cmd))
+    {
+        Some(future) => future,
+        None => {
+            editor.set_error("Language server does not support executing commands");
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        if let Err(err) = future.await {
+            log::error!("execute LSP command: {}", err);
+        }
+    });
+}
+
+// mostly copy pasted from helix_term/src/commands/lsp.rs
+fn apply_code_action(
+    editor: &mut Editor,
+    language_server_id: LanguageServerId,
+    code_action: &lsp::CodeAction,
+) {
+    let Some(language_server) = editor.language_server_by_id(language_server_id) else {
+        editor.set_error("Language Server disappeared");
+        return;
+    };
+    let offset_encoding = language_server.offset_encoding();
+
+    if let Some(workspace_edit) = &code_action.edit {
+        if let Err(err) = editor.apply_workspace_edit(offset_encoding, workspace_edit) {
+            log::error!("failed to apply code action: {:?}", err);
+        }
+    }
+
+    // if code action provides both edit and command first the edit
+    // should be applied and then the command
+    if let Some(command) = &code_action.command {
+        execute_lsp_command(editor, language_server_id, command.clone());
+    }
+}
+
+/// Native replacement of helix's `code_action` command, bound in the keymap.
+pub fn code_action(cx: &mut commands::Context) {
+    request_code_actions(cx.editor);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffLineKind {
+    File,
+    Hunk,
+    Removed,
+    Added,
+}
+
+#[derive(Debug, Clone)]
+struct DiffLine {
+    kind: DiffLineKind,
+    text: SharedString,
+}
+
+impl DiffLine {
+    fn new(kind: DiffLineKind, text: impl Into<SharedString>) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+        }
+    }
+}
+
+// contents of `path`, from the open document if there is one
+fn file_text(editor: &Editor, path: &std::path::Path) -> Option<Rope> {
+    if let Some(doc) = editor.document_by_path(path) {
+        return Some(doc.text().clone());
+    }
+    std::fs::read_to_string(path).ok().map(Rope::from)
+}
+
+fn display_uri(uri: &lsp::Url) -> String {
+    match uri.to_file_path() {
+        Ok(path) => get_relative_path(&path).display().to_string(),
+        Err(_) => uri.to_string(),
+    }
+}
+
+/// Lines touched by each edit before and after it is applied, every edit
+/// is shown as its own hunk.
+fn diff_text_edits(
+    editor: &Editor,
+    uri: &lsp::Url,
+    mut edits: Vec<&lsp::TextEdit>,
+    offset_encoding: OffsetEncoding,
+    lines: &mut Vec<DiffLine>,
+) {
+    lines.push(DiffLine::new(DiffLineKind::File, display_uri(uri)));
+    let Some(text) = uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| file_text(editor, &path))
+    else {
+        return;
+    };
+
+    edits.sort_by_key(|edit| edit.range.start);
+    for edit in edits {
+        let start = util::lsp_pos_to_pos(&text, edit.range.start, offset_encoding);
+        let end = util::lsp_pos_to_pos(&text, edit.range.end, offset_encoding);
+        let (Some(start), Some(end)) = (start, end) else {
+            continue;
+        };
+        let first_line = text.char_to_line(start);
+        let last_line = text.char_to_line(end);
+        let line_start = text.line_to_char(first_line);
+        let line_end = if last_line + 1 < text.len_lines() {
+            text.line_to_char(last_line + 1)
+        } else {
+            text.len_chars()
+        };
+
+        let old = text.slice(line_start..line_end).to_string();
+        let new = format!(
+            "{}{}{}",
+            text.slice(line_start..start),
+            edit.new_text,
+            text.slice(end..line_end)
+        );
+        lines.push(DiffLine::new(
+            DiffLineKind::Hunk,
+            format!("@@ line {} @@", first_line + 1),
+        ));
+        lines.extend(
+            old.lines()
+                .map(|line| DiffLine::new(DiffLineKind::Removed, format!("-{line}"))),
+        );
+        lines.extend(
+            new.lines()
+                .map(|line| DiffLine::new(DiffLineKind::Added, format!("+{line}"))),
+        );
+    }
+}
+
+fn diff_workspace_edit(
+    editor: &Editor,
+    edit: &lsp::WorkspaceEdit,
+    offset_encoding: OffsetEncoding,
+) -> Vec<DiffLine> {
+    let mut lines = Vec::new();
+    if let Some(document_changes) = &edit.document_changes {
+        let text_edits = |edit: &lsp::TextDocumentEdit| {
+            edit.edits
+                .iter()
+                .map(|edit| match edit {
+                    lsp::OneOf::Left(edit) => edit,
+                    lsp::OneOf::Right(edit) => &edit.text_edit,
+                })
+                .collect::<Vec<_>>()
+        };
+        match document_changes {
+            lsp::DocumentChanges::Edits(edits) => {
+                for edit in edits {
+                    let uri = &edit.text_document.uri;
+                    diff_text_edits(editor, uri, text_edits(edit), offset_encoding, &mut lines);
+                }
+            }
+            lsp::DocumentChanges::Operations(operations) => {
+                for operation in operations {
+                    match operation {
+                        lsp::DocumentChangeOperation::Edit(edit) => {
+                            let uri = &edit.text_document.uri;
+                            diff_text_edits(
+                                editor,
+                                uri,
+                                text_edits(edit),
+                                offset_encoding,
+                                &mut lines,
+                            );
+                        }
+                        lsp::DocumentChangeOperation::Op(op) => {
+                            let text = match op {
+                                lsp::ResourceOp::Create(op) => {
+                                    format!("create {}", display_uri(&op.uri))
+                                }
+                                lsp::ResourceOp::Rename(op) => format!(
+                                    "rename {} -> {}",
+                                    display_uri(&op.old_uri),
+                                    display_uri(&op.new_uri)
+                                ),
+                                lsp::ResourceOp::Delete(op) => {
+                                    format!("delete {}", display_uri(&op.uri))
+                                }
+                            };
+                            lines.push(DiffLine::new(DiffLineKind::File, text));
+                        }
+                    }
+                }
+            }
+        }
+    } else if let Some(changes) = &edit.changes {
+        for (uri, edits) in changes {
+            diff_text_edits(
+                editor,
+                uri,
+                edits.iter().collect(),
+                offset_encoding,
+                &mut lines,
+            );
+        }
+    }
+    lines.truncate(MAX_PREVIEW_LINES);
+    lines
+}
+
+/// Code action menu, lives in the compositor so that it gets the keys
+/// while it is open. It is rendered natively by the document view.
+pub struct CodeActions {
+    items: Vec<CodeActionItem>,
+    cursor: usize,
+    view_id: ViewId,
+    // char the menu is anchored at
+    anchor: usize,
+    // edit preview of the item at the cursor
+    preview: Option<(usize, Vec<DiffLine>)>,
+}
+
+impl CodeActions {
+    pub const ID: &'static str = "native-code-action";
+
+    fn move_up(&mut self) {
+        self.cursor = self
+            .cursor
+            .checked_sub(1)
+            .unwrap_or(self.items.len().saturating_sub(1));
+    }
+
+    fn move_down(&mut self) {
+        self.cursor = (self.cursor + 1) % self.items.len().max(1);
+    }
+
+    // servers may leave the edit out until the action is resolved,
+    // fetch it in the background so that it can be previewed
+    fn resolve_selected(&mut self, editor: &Editor) {
+        let cursor = self.cursor;
+        let Some(item) = self.items.get_mut(cursor) else {
+            return;
+        };
+        if item.resolved {
+            return;
+        }
+        item.resolved = true;
+        let lsp::CodeActionOrCommand::CodeAction(action) = &item.action else {
+            return;
+        };
+        if action.edit.is_some() {
+            return;
+        }
+        let Some(future) = editor
+            .language_server_by_id(item.language_server_id)
+            .and_then(|language_server| language_server.resolve_code_action(action.clone()))
+        else {
+            return;
+        };
+        let title = action.title.clone();
+        tokio::spawn(async move {
+            let resolved = match future.await {
+                Ok(json) => serde_json::from_value::<lsp::CodeAction>(json),
+                Err(err) => {
+                    log::error!("failed to resolve code action {}: {}", title, err);
+                    return;
+                }
+            };
+            let Ok(resolved) = resolved else {
+                return;
+            };
+            dispatch(move |_editor, compositor| {
+                if let Some(code_actions) = compositor.find::<CodeActions>() {
+                    if let Some(item) = code_actions.items.get_mut(cursor) {
+                        if item.title() == resolved.title {
+                            item.action = lsp::CodeActionOrCommand::CodeAction(resolved);
+                            code_actions.preview = None;
+                        }
+                    }
+                }
+            })
+            .await
+        });
+    }
+
+    // mostly copy pasted from helix_term/src/commands/lsp.rs
+    fn apply(&self, editor: &mut Editor) {
+        let Some(item) = self.items.get(self.cursor) else {
+            return;
+        };
+        let language_server_id = item.language_server_id;
+        let Some(language_server) = editor.language_server_by_id(language_server_id) else {
+            editor.set_error("Language Server disappeared");
+            return;
+        };
+
+        let code_action = match &item.action {
+            lsp::CodeActionOrCommand::Command(command) => {
+                log::debug!("code action command: {:?}", command);
+                execute_lsp_command(editor, language_server_id, command.clone());
+                return;
+            }
+            lsp::CodeActionOrCommand::CodeAction(code_action) => code_action.clone(),
+        };
+        log::debug!("code action: {:?}", code_action);
+
+        // the background resolve may not have finished yet
+        let future = (code_action.edit.is_none() || code_action.command.is_none())
+            .then(|| language_server.resolve_code_action(code_action.clone()))
+            .flatten();
+        let Some(future) = future else {
+            apply_code_action(editor, language_server_id, &code_action);
+            return;
+        };
+        tokio::spawn(async move {
+            let resolved = match future.await {
+                Ok(json) => serde_json::from_value::<lsp::CodeAction>(json).ok(),
+                Err(err) => {
+                    log::error!(
+                        "failed to resolve code action {}: {}",
+                        code_action.title,
+                        err
+                    );
+                    None
+                }
+            };
+            let code_action = resolved.unwrap_or(code_action);
+            dispatch(move |editor, _compositor| {
+                apply_code_action(editor, language_server_id, &code_action)
+            })
+            .await
+        });
+    }
+
+    fn preview(&mut self, editor: &Editor) -> Vec<DiffLine> {
+        if let Some((cursor, lines)) = &self.preview {
+            if *cursor == self.cursor {
+                return lines.clone();
+            }
+        }
+        let lines = self
+            .items
+            .get(self.cursor)
+            .and_then(|item| {
+                let edit = item.edit()?;
+                let language_server = editor.language_server_by_id(item.language_server_id)?;
+                Some(diff_workspace_edit(
+                    editor,
+                    edit,
+                    language_server.offset_encoding(),
+                ))
+            })
+            .unwrap_or_default();
+        self.preview = Some((self.cursor, lines.clone()));
+        lines
+    }
+
+    /// Snapshot of the menu for rendering.
+    pub fn menu(&mut self, editor: &Editor) -> CodeActionMenu {
+        let theme = &editor.theme;
+        let menu_style = theme.get("ui.menu");
+        let selected_style = theme.get("ui.menu.selected");
+        let group_style = theme.get("ui.text.inactive");
+        let popup_style = theme.get("ui.popup");
+        let text_style = theme.get("ui.text");
+
+        let fg = menu_style
+            .fg
+            .or(text_style.fg)
+            .and_then(color_to_hsla)
+            .unwrap_or(white());
+        let bg = menu_style
+            .bg
+            .or(popup_style.bg)
+            .and_then(color_to_hsla)
+            .unwrap_or(black());
+
+        let mut groups: Vec<CodeActionGroup> = Vec::new();
+        for (idx, item) in self.items.iter().enumerate() {
+            let group = item.group();
+            let entry = CodeActionMenuItem {
+                title: item.title().to_string().into(),
+                selected: idx == self.cursor,
+            };
+            match groups.last_mut() {
+                Some(last) if last.group == group => last.items.push(entry),
+                _ => groups.push(CodeActionGroup {
+                    group,
+                    items: vec![entry],
+                }),
+            }
+        }
+
+        CodeActionMenu {
+            view_id: self.view_id,
+            anchor: self.anchor,
+            groups,
+            preview: self.preview(editor),
+            fg,
+            bg,
+            selected_fg: selected_style.fg.and_then(color_to_hsla).unwrap_or(fg),
+            selected_bg: selected_style.bg.and_then(color_to_hsla).unwrap_or(bg),
+            group_fg: group_style.fg.and_then(color_to_hsla).unwrap_or(fg),
+            popup_bg: popup_style.bg.and_then(color_to_hsla).unwrap_or(bg),
+            added_fg: theme.get("diff.plus").fg.and_then(color_to_hsla),
+            removed_fg: theme.get("diff.minus").fg.and_then(color_to_hsla),
+        }
+    }
+}
+
+impl Component for CodeActions {
+    fn handle_event(&mut self, event: &Event, cx: &mut compositor::Context) -> EventResult {
+        let close_fn: compositor::Callback = Box::new(|compositor, _| {
+            compositor.remove(Self::ID);
+        });
+
+        let Event::Key(event) = event else {
+            return EventResult::Ignored(None);
+        };
+
+        match *event {
+            key!(Esc) | ctrl!('c') => EventResult::Consumed(Some(close_fn)),
+            shift!(Tab) | key!(Up) | ctrl!('p') | key!('k') => {
+                self.move_up();
+                self.resolve_selected(cx.editor);
+                EventResult::Consumed(None)
+            }
+            key!(Tab) | key!(Down) | ctrl!('n') | key!('j') => {
+                self.move_down();
+                self.resolve_selected(cx.editor);
+                EventResult::Consumed(None)
+            }
+            key!(Enter) => {
+                self.apply(cx.editor);
+                EventResult::Consumed(Some(close_fn))
+            }
+            _ => EventResult::Ignored(Some(close_fn)),
+        }
+    }
+
+    fn render(
+        &mut self,
+        _area: helix_view::graphics::Rect,
+        _frame: &mut tui::buffer::Buffer,
+        _ctx: &mut compositor::Context,
+    ) {
+        // rendered by the document view
+    }
+
+    fn id(&self) -> Option<&'static str> {
+        Some(Self::ID)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CodeActionMenuItem {
+    title: SharedString,
+    selected: bool,
+}
+
+#[derive(Debug, Clone)]
+struct CodeActionGroup {
+    group: Group,
+    items: Vec<CodeActionMenuItem>,
+}
+
+#[derive(Debug, Clone, IntoElement)]
+pub struct CodeActionMenu {
+    pub view_id: ViewId,
+    /// char index the menu is anchored at
+    pub anchor: usize,
+    groups: Vec<CodeActionGroup>,
+    preview: Vec<DiffLine>,
+    fg: Hsla,
+    bg: Hsla,
+    selected_fg: Hsla,
+    selected_bg: Hsla,
+    group_fg: Hsla,
+    popup_bg: Hsla,
+    added_fg: Option<Hsla>,
+    removed_fg: Option<Hsla>,
+}
+
+impl RenderOnce for CodeActionMenu {
+    fn render(self, cx: &mut WindowContext) -> impl IntoElement {
+        let font = cx.global::<crate::FontSettings>().fixed_font.clone();
+        let style = cx.global::<crate::FontSettings>().ui_text_style();
+        let groups = self.groups.into_iter().map(|group| {
+            let items = group.items.into_iter().map(|item| {
+                let (fg, bg) = if item.selected {
+                    (self.selected_fg, self.selected_bg)
+                } else {
+                    (self.fg, self.bg)
+                };
+                div()
+                    .px_2()
+                    .text_color(fg)
+                    .bg(bg)
+                    .whitespace_nowrap()
+                    .child(item.title)
+            });
+            div()
+                .flex()
+                .flex_col()
+                .child(
+                    div()
+                        .px_1()
+                        .text_color(self.group_fg)
+                        .child(group.group.label()),
+                )
+                .children(items)
+        });
+
+        let preview = (!self.preview.is_empty()).then(|| {
+            let lines = self.preview.into_iter().map(|line| {
+                let color = match line.kind {
+                    DiffLineKind::File | DiffLineKind::Hunk => Some(self.group_fg),
+                    DiffLineKind::Added => self.added_fg,
+                    DiffLineKind::Removed => self.removed_fg,
+                };
+                div()
+                    .whitespace_nowrap()
+                    .when_some(color, |this, color| this.text_color(color))
+                    .when(line.kind == DiffLineKind::File, |this| {
+                        this.font_weight(FontWeight::BOLD)
+                    })
+                    .child(line.text)
+            });
+            div()
+                .id("code-action-preview")
+                .flex()
+                .flex_col()
+                .max_w(px(600.))
+                .max_h(px(300.))
+                .overflow_y_scroll()
+                .p_2()
+                .shadow_sm()
+                .rounded_sm()
+                .text_color(self.fg)
+                .bg(self.popup_bg)
+                .children(lines)
+        });
+
+        div()
+            .flex()
+            .flex_row()
+            .items_start()
+            .gap_1()
+            .font(font)
+            .text_size(style.font_size)
+            .line_height(style.line_height)
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .gap_1()
+                    .py_1()
+                    .min_w(px(200.))
+                    .shadow_sm()
+                    .rounded_sm()
+                    .bg(self.bg)
+                    .children(groups),
+            )
+            .when_some(preview, |this, preview| this.child(preview))
+    }
+}
@@ -0,0 +1,1168 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use helix_core::syntax::LanguageServerFeature;
+use helix_core::{Position, Selection};
+use helix_lsp::{lsp, util::lsp_range_to_range, LanguageServerId, OffsetEncoding};
+use helix_stdx::path::{get_relative_path, get_truncated_path};
+use helix_term::commands::{Context, MappableCommand, TYPABLE_COMMAND_LIST};
+use helix_term::compositor::{self, AnyComponent, Component, Compositor, EventResult};
+use helix_term::job::dispatch;
+use helix_term::keymap::{KeyTrie, ReverseKeymap};
+use helix_term::ui::{self, menu::Item, PromptEvent};
+use helix_vcs::FileChange;
+use helix_view::document::{Mode, SCRATCH_BUFFER_NAME};
+use helix_view::editor::Action;
+use helix_view::graphics::{CursorKind, Rect, Style};
+use helix_view::input::Event;
+use helix_view::{align_view, current, doc, doc_mut, view, view_mut, Align, DocumentId, Editor};
+use tui::text::{Span, Spans};
+use tui::widgets::{Cell, Row};
+
+use crate::picker::{self, FileLocation, Picker};
+
+type CommandFn = fn(&mut Context);
+
+/// helix commands replaced by native versions, by name.
+const NATIVE_COMMANDS: &[(&str, CommandFn)] = &[
+    ("file_picker", file_picker),
+    (
+        "file_picker_in_current_directory",
+        file_picker_in_current_directory,
+    ),
+    (
+        "file_picker_in_current_buffer_directory",
+        file_picker_in_current_buffer_directory,
+    ),
+    ("buffer_picker", buffer_picker),
+    ("jumplist_picker", jumplist_picker),
+    ("symbol_picker", symbol_picker),
+    ("workspace_symbol_picker", workspace_symbol_picker),
+    ("diagnostics_picker", diagnostics_picker),
+    ("workspace_diagnostics_picker", workspace_diagnostics_picker),
+    ("changed_file_picker", changed_file_picker),
+    ("global_search", global_search),
+    ("command_palette", command_palette),
+    ("last_picker", last_picker),
+    ("goto_definition", goto_definition),
+    ("goto_declaration", goto_declaration),
+    ("goto_type_definition", goto_type_definition),
+    ("goto_implementation", goto_implementation),
+    ("goto_reference", goto_reference),
+    ("command_mode", command_mode),
+    ("hover", crate::hover::hover),
+    ("code_action", crate::code_action::code_action),
+];
+
+thread_local! {
+    // keymap with the native commands, for the command palette
+    static KEYMAP: RefCell<HashMap<Mode, KeyTrie>> = RefCell::new(HashMap::new());
+}
+
+/// Binds the native versions of helix's commands wherever the keymap uses them,
+/// so that helix never opens its TUI pickers and popups.
+pub fn replace_commands(keys: &mut HashMap<Mode, KeyTrie>) {
+    for trie in keys.values_mut() {
+        replace_in_trie(trie);
+    }
+    KEYMAP.with(|keymap| *keymap.borrow_mut() = keys.clone());
+}
+
+fn replace_in_trie(trie: &mut KeyTrie) {
+    match trie {
+        KeyTrie::MappableCommand(command) => replace_command(command),
+        KeyTrie::Sequence(commands) => commands.iter_mut().for_each(replace_command),
+        KeyTrie::Node(node) => node.values_mut().for_each(replace_in_trie),
+    }
+}
+
+fn replace_command(command: &mut MappableCommand) {
+    if let MappableCommand::Static { name, doc, .. } = *command {
+        if let Some((_, fun)) = NATIVE_COMMANDS.iter().find(|(native, _)| *native == name) {
+            *command = MappableCommand::Static {
+                name,
+                fun: *fun,
+                doc,
+            };
+        }
+    }
+}
+
+fn file_picker(cx: &mut Context) {
+    let root = helix_loader::find_workspace().0;
+    if !root.exists() {
+        cx.editor.set_error("Workspace directory does not exist");
+        return;
+    }
+    let picker = picker::file_picker(root, &cx.editor.config());
+    cx.push_layer(Box::new(picker));
+}
+
+fn file_picker_in_current_directory(cx: &mut Context) {
+    let cwd = helix_stdx::env::current_working_dir();
+    if !cwd.exists() {
+        cx.editor
+            .set_error("Current working directory does not exist");
+        return;
+    }
+    let picker = picker::file_picker(cwd, &cx.editor.config());
+    cx.push_layer(Box::new(picker));
+}
+
+fn file_picker_in_current_buffer_directory(cx: &mut Context) {
+    let doc_dir = doc!(cx.editor)
+        .path()
+        .and_then(|path| path.parent().map(Path::to_path_buf));
+    let Some(path) = doc_dir else {
+        cx.editor.set_error("current buffer has no path or parent");
+        return;
+    };
+    let picker = picker::file_picker(path, &cx.editor.config());
+    cx.push_layer(Box::new(picker));
+}
+
+fn display_path(path: Option<&Path>) -> String {
+    path.map(get_relative_path)
+        .as_deref()
+        .and_then(Path::to_str)
+        .unwrap_or(SCRATCH_BUFFER_NAME)
+        .to_string()
+}
+
+struct BufferMeta {
+    id: DocumentId,
+    path: Option<PathBuf>,
+    is_modified: bool,
+    is_current: bool,
+    focused_at: std::time::Instant,
+}
+
+impl Item for BufferMeta {
+    type Data = ();
+
+    fn format(&self, _data: &Self::Data) -> Row {
+        let mut flags = String::new();
+        if self.is_modified {
+            flags.push('+');
+        }
+        if self.is_current {
+            flags.push('*');
+        }
+        Row::new([
+            self.id.to_string(),
+            flags,
+            display_path(self.path.as_deref()),
+        ])
+    }
+}
+
+// mostly copy pasted from helix_term/src/commands.rs
+fn buffer_picker(cx: &mut Context) {
+    let current = view!(cx.editor).doc;
+
+    let mut items = cx
+        .editor
+        .documents()
+        .map(|doc| BufferMeta {
+            id: doc.id(),
+            path: doc.path().cloned(),
+            is_modified: doc.is_modified(),
+            is_current: doc.id() == current,
+            focused_at: doc.focused_at,
+        })
+        .collect::<Vec<_>>();
+    // most recently used first
+    items.sort_unstable_by_key(|item| std::cmp::Reverse(item.focused_at));
+
+    let (picker, _) = Picker::new(
+        items,
+        (),
+        |cx, meta: &BufferMeta, action| {
+            cx.editor.switch(meta.id, action);
+        },
+        Some(Box::new(|editor: &Editor, meta: &BufferMeta| {
+            let doc = editor.document(meta.id)?;
+            let &view_id = doc.selections().keys().next()?;
+            let line = doc
+                .selection(view_id)
+                .primary()
+                .cursor_line(doc.text().slice(..));
+            Some((doc.path()?.clone(), Some((line, line))))
+        })),
+    );
+    cx.push_layer(Box::new(picker));
+}
+
+struct JumpMeta {
+    id: DocumentId,
+    path: Option<PathBuf>,
+    selection: Selection,
+    text: String,
+    is_current: bool,
+}
+
+impl Item for JumpMeta {
+    type Data = ();
+
+    fn format(&self, _data: &Self::Data) -> Row {
+        let flag = if self.is_current { " (*)" } else { "" };
+        format!(
+            "{} {}{} {}",
+            self.id,
+            display_path(self.path.as_deref()),
+            flag,
+            self.text
+        )
+        .into()
+    }
+}
+
+// mostly copy pasted from helix_term/src/commands.rs
+fn jumplist_picker(cx: &mut Context) {
+    for (view, _) in cx.editor.tree.views_mut() {
+        for doc_id in view.jumps.iter().map(|e| e.0).collect::<Vec<_>>().iter() {
+            let doc = doc_mut!(cx.editor, doc_id);
+            view.sync_changes(doc);
+        }
+    }
+
+    let editor = &*cx.editor;
+    let items = editor
+        .tree
+        .views()
+        .flat_map(|(view, _)| {
+            view.jumps.iter().rev().map(move |(doc_id, selection)| {
+                let doc = editor.document(*doc_id);
+                let text = doc.map_or(String::new(), |doc| {
+                    selection
+                        .fragments(doc.text().slice(..))
+                        .map(Cow::into_owned)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                });
+                JumpMeta {
+                    id: *doc_id,
+                    path: doc.and_then(|doc| doc.path().cloned()),
+                    selection: selection.clone(),
+                    text,
+                    is_current: view.doc == *doc_id,
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let (picker, _) = Picker::new(
+        items,
+        (),
+        |cx, meta: &JumpMeta, action| {
+            cx.editor.switch(meta.id, action);
+            let (view, doc) = (view_mut!(cx.editor), doc_mut!(cx.editor, &meta.id));
+            doc.set_selection(view.id, meta.selection.clone());
+            if action.align_view(view, doc.id()) {
+                align_view(doc, view, Align::Center);
+            }
+        },
+        Some(Box::new(|editor: &Editor, meta: &JumpMeta| {
+            let doc = editor.document(meta.id)?;
+            let line = meta.selection.primary().cursor_line(doc.text().slice(..));
+            Some((doc.path()?.clone(), Some((line, line))))
+        })),
+    );
+    cx.push_layer(Box::new(picker));
+}
+
+fn push_jump(editor: &mut Editor) {
+    let (view, doc) = current!(editor);
+    let jump = (doc.id(), doc.selection(view.id).clone());
+    view.jumps.push(jump);
+}
+
+// mostly copy pasted from helix_term/src/commands/lsp.rs
+fn jump_to_location(
+    editor: &mut Editor,
+    location: &lsp::Location,
+    offset_encoding: OffsetEncoding,
+    action: Action,
+) {
+    push_jump(editor);
+    let Ok(path) = location.uri.to_file_path() else {
+        editor.set_error(format!(
+            "unable to convert URI to filepath: {}",
+            location.uri
+        ));
+        return;
+    };
+    jump_to_position(editor, &path, location.range, offset_encoding, action);
+}
+
+fn jump_to_position(
+    editor: &mut Editor,
+    path: &Path,
+    range: lsp::Range,
+    offset_encoding: OffsetEncoding,
+    action: Action,
+) {
+    let doc_id = match editor.open(path, action) {
+        Ok(doc_id) => doc_id,
+        Err(err) => {
+            editor.set_error(format!("failed to open path: {:?}: {:?}", path, err));
+            return;
+        }
+    };
+    let view = view_mut!(editor);
+    let doc = doc_mut!(editor, &doc_id);
+    let Some(range) = lsp_range_to_range(doc.text(), range, offset_encoding) else {
+        log::warn!("lsp position out of bounds - {:?}", range);
+        return;
+    };
+    // we flip the range so that the cursor sits on the start of the symbol
+    doc.set_selection(view.id, Selection::single(range.head, range.anchor));
+    if action.align_view(view, doc.id()) {
+        align_view(doc, view, Align::Center);
+    }
+}
+
+fn location_to_file_location(location: &lsp::Location) -> Option<FileLocation> {
+    let path = location.uri.to_file_path().ok()?;
+    let lines = (
+        location.range.start.line as usize,
+        location.range.end.line as usize,
+    );
+    Some((path, Some(lines)))
+}
+
+struct SymbolItem {
+    symbol: lsp::SymbolInformation,
+    offset_encoding: OffsetEncoding,
+}
+
+impl Item for SymbolItem {
+    // url of the current document, its symbols are shown without a path
+    type Data = Option<lsp::Url>;
+
+    fn format(&self, current_url: &Self::Data) -> Row {
+        let uri = &self.symbol.location.uri;
+        if current_url.as_ref() == Some(uri) {
+            return self.symbol.name.as_str().into();
+        }
+        match uri.to_file_path() {
+            Ok(path) => format!(
+                "{} ({})",
+                self.symbol.name,
+                get_relative_path(&path).to_string_lossy()
+            )
+            .into(),
+            Err(_) => format!("{} ({})", self.symbol.name, uri).into(),
+        }
+    }
+}
+
+fn symbol_picker_of(symbols: Vec<SymbolItem>, current_url: Option<lsp::Url>) -> Picker {
+    let (picker, _) = Picker::new(
+        symbols,
+        current_url,
+        |cx, item: &SymbolItem, action| {
+            jump_to_location(
+                cx.editor,
+                &item.symbol.location,
+                item.offset_encoding,
+                action,
+            );
+        },
+        Some(Box::new(|_editor: &Editor, item: &SymbolItem| {
+            location_to_file_location(&item.symbol.location)
+        })),
+    );
+    picker
+}
+
+// flattens the nested variant of the response, so that pickers get a single list
+#[allow(deprecated)]
+fn nested_to_flat(
+    list: &mut Vec<SymbolItem>,
+    file: &lsp::TextDocumentIdentifier,
+    symbol: lsp::DocumentSymbol,
+    offset_encoding: OffsetEncoding,
+) {
+    list.push(SymbolItem {
+        symbol: lsp::SymbolInformation {
+            name: symbol.name,
+            kind: symbol.kind,
+            tags: symbol.tags,
+            deprecated: symbol.deprecated,
+            location: lsp::Location::new(file.uri.clone(), symbol.selection_range),
+            container_name: None,
+        },
+        offset_encoding,
+    });
+    for child in symbol.children.into_iter().flatten() {
+        nested_to_flat(list, file, child, offset_encoding);
+    }
+}
+
+// mostly copy pasted from helix_term/src/commands/lsp.rs
+fn symbol_picker(cx: &mut Context) {
+    let doc = doc!(cx.editor);
+    let mut seen_language_servers = HashSet::new();
+    let futures: Vec<_> = doc
+        .language_servers_with_feature(LanguageServerFeature::DocumentSymbols)
+        .filter(|ls| seen_language_servers.insert(ls.id()))
+        .filter_map(|language_server| {
+            let future = language_server.document_symbols(doc.identifier())?;
+            let offset_encoding = language_server.offset_encoding();
+            let doc_id = doc.identifier();
+            Some(async move {
+                let json = future.await?;
+                let response: Option<lsp::DocumentSymbolResponse> = serde_json::from_value(json)?;
+                let symbols = match response {
+                    Some(lsp::DocumentSymbolResponse::Flat(symbols)) => symbols
+                        .into_iter()
+                        .map(|symbol| SymbolItem {
+                            symbol,
+                            offset_encoding,
+                        })
+                        .collect(),
+                    Some(lsp::DocumentSymbolResponse::Nested(symbols)) => {
+                        let mut flat_symbols = Vec::new();
+                        for symbol in symbols {
+                            nested_to_flat(&mut flat_symbols, &doc_id, symbol, offset_encoding);
+                        }
+                        flat_symbols
+                    }
+                    None => Vec::new(),
+                };
+                anyhow::Ok(symbols)
+            })
+        })
+        .collect();
+
+    if futures.is_empty() {
+        cx.editor
+            .set_error("No configured language server supports document symbols");
+        return;
+    }
+
+    let current_url = doc.url();
+    tokio::spawn(async move {
+        let mut symbols = Vec::new();
+        for response in futures_util::future::join_all(futures).await {
+            match response {
+                Ok(items) => symbols.extend(items),
+                Err(err) => log::error!("document symbols request failed: {err}"),
+            }
+        }
+        dispatch(move |_editor, compositor| {
+            compositor.push(Box::new(symbol_picker_of(symbols, current_url)));
+        })
+        .await
+    });
+}
+
+fn workspace_symbols(
+    query: &str,
+    editor: &mut Editor,
+) -> Option<futures_util::future::BoxFuture<'static, anyhow::Result<Vec<SymbolItem>>>> {
+    let doc = doc!(editor);
+    let mut seen_language_servers = HashSet::new();
+    let futures: Vec<_> = doc
+        .language_servers_with_feature(LanguageServerFeature::WorkspaceSymbols)
+        .filter(|ls| seen_language_servers.insert(ls.id()))
+        .filter_map(|language_server| {
+            let future = language_server.workspace_symbols(query.to_string())?;
+            let offset_encoding = language_server.offset_encoding();
+            Some(async move {
+                let json = future.await?;
+                let response: Option<Vec<lsp::SymbolInformation>> = serde_json::from_value(json)?;
+                let symbols = response
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|symbol| SymbolItem {
+                        symbol,
+                        offset_encoding,
+                    })
+                    .collect::<Vec<_>>();
+                anyhow::Ok(symbols)
+            })
+        })
+        .collect();
+    if futures.is_empty() {
+        return None;
+    }
+
+    Some(Box::pin(async move {
+        let mut symbols = Vec::new();
+        for response in futures_util::future::join_all(futures).await {
+            symbols.extend(response?);
+        }
+        Ok(symbols)
+    }))
+}
+
+// mostly copy pasted from helix_term/src/commands/lsp.rs
+fn workspace_symbol_picker(cx: &mut Context) {
+    let Some(future) = workspace_symbols("", cx.editor) else {
+        cx.editor
+            .set_error("No configured language server supports workspace symbols");
+        return;
+    };
+
+    let current_url = doc!(cx.editor).url();
+    tokio::spawn(async move {
+        let symbols = match future.await {
+            Ok(symbols) => symbols,
+            Err(err) => {
+                log::error!("workspace symbols request failed: {err}");
+                return;
+            }
+        };
+        dispatch(move |_editor, compositor| {
+            // servers only send some of the symbols, ask again as the query changes
+            let picker =
+                symbol_picker_of(symbols, current_url).with_query_fn(Box::new(workspace_symbols));
+            compositor.push(Box::new(picker));
+        })
+        .await
+    });
+}
+
+struct PickerDiagnostic {
+    path: PathBuf,
+    diag: lsp::Diagnostic,
+    offset_encoding: OffsetEncoding,
+}
+
+struct DiagnosticStyles {
+    hint: Style,
+    info: Style,
+    warning: Style,
+    error: Style,
+    // the workspace picker shows which file each diagnostic is in
+    show_path: bool,
+}
+
+impl Item for PickerDiagnostic {
+    type Data = DiagnosticStyles;
+
+    fn format(&self, styles: &Self::Data) -> Row {
+        let mut style = match self.diag.severity {
+            Some(lsp::DiagnosticSeverity::HINT) => styles.hint,
+            Some(lsp::DiagnosticSeverity::INFORMATION) => styles.info,
+            Some(lsp::DiagnosticSeverity::WARNING) => styles.warning,
+            Some(lsp::DiagnosticSeverity::ERROR) => styles.error,
+            _ => Style::default(),
+        };
+        // the background is distracting in the list
+        style.bg = None;
+
+        let code = match self.diag.code.as_ref() {
+            Some(lsp::NumberOrString::Number(n)) => format!(" ({n})"),
+            Some(lsp::NumberOrString::String(s)) => format!(" ({s})"),
+            None => String::new(),
+        };
+        let path = if styles.show_path {
+            format!("{}: ", get_truncated_path(&self.path).to_string_lossy())
+        } else {
+            String::new()
+        };
+
+        Spans::from(vec![
+            Span::raw(path),
+            Span::styled(self.diag.message.clone(), style),
+            Span::styled(code, style),
+        ])
+        .into()
+    }
+}
+
+// mostly copy pasted from helix_term/src/commands/lsp.rs
+fn diagnostics_picker_of(
+    editor: &Editor,
+    diagnostics: BTreeMap<PathBuf, Vec<(lsp::Diagnostic, LanguageServerId)>>,
+    show_path: bool,
+) -> Picker {
+    let mut items = Vec::new();
+    for (path, diags) in diagnostics {
+        for (diag, server_id) in diags {
+            if let Some(language_server) = editor.language_server_by_id(server_id) {
+                items.push(PickerDiagnostic {
+                    path: path.clone(),
+                    diag,
+                    offset_encoding: language_server.offset_encoding(),
+                });
+            }
+        }
+    }
+
+    let theme = &editor.theme;
+    let styles = DiagnosticStyles {
+        hint: theme.get("hint"),
+        info: theme.get("info"),
+        warning: theme.get("warning"),
+        error: theme.get("error"),
+        show_path,
+    };
+
+    let (picker, _) = Picker::new(
+        items,
+        styles,
+        |cx, item: &PickerDiagnostic, action| {
+            push_jump(cx.editor);
+            jump_to_position(
+                cx.editor,
+                &item.path,
+                item.diag.range,
+                item.offset_encoding,
+                action,
+            );
+        },
+        Some(Box::new(|_editor: &Editor, item: &PickerDiagnostic| {
+            let range = &item.diag.range;
+            let lines = (range.start.line as usize, range.end.line as usize);
+            Some((item.path.clone(), Some(lines)))
+        })),
+    );
+    picker
+}
+
+fn diagnostics_picker(cx: &mut Context) {
+    let Some(path) = doc!(cx.editor).path().cloned() else {
+        return;
+    };
+    let diagnostics = cx
+        .editor
+        .diagnostics
+        .get(&path)
+        .cloned()
+        .unwrap_or_default();
+    let picker = diagnostics_picker_of(cx.editor, [(path, diagnostics)].into(), false);
+    cx.push_layer(Box::new(picker));
+}
+
+fn workspace_diagnostics_picker(cx: &mut Context) {
+    let diagnostics = cx.editor.diagnostics.clone();
+    let picker = diagnostics_picker_of(cx.editor, diagnostics, true);
+    cx.push_layer(Box::new(picker));
+}
+
+struct ChangedFile(FileChange);
+
+struct ChangedFileStyles {
+    cwd: PathBuf,
+    untracked: Style,
+    modified: Style,
+    conflict: Style,
+    deleted: Style,
+    renamed: Style,
+}
+
+impl Item for ChangedFile {
+    type Data = ChangedFileStyles;
+
+    fn format(&self, styles: &Self::Data) -> Row {
+        let relative = |path: &PathBuf| {
+            path.strip_prefix(&styles.cwd)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        let (sign, style, content) = match &self.0 {
+            FileChange::Untracked { path } => ("[+]", styles.untracked, relative(path)),
+            FileChange::Modified { path } => ("[~]", styles.modified, relative(path)),
+            FileChange::Conflict { path } => ("[x]", styles.conflict, relative(path)),
+            FileChange::Deleted { path } => ("[-]", styles.deleted, relative(path)),
+            FileChange::Renamed { from_path, to_path } => (
+                "[>]",
+                styles.renamed,
+                format!("{} -> {}", relative(from_path), relative(to_path)),
+            ),
+        };
+        Row::new([Cell::from(Span::styled(sign, style)), Cell::from(content)])
+    }
+}
+
+// mostly copy pasted from helix_term/src/commands.rs
+fn changed_file_picker(cx: &mut Context) {
+    let cwd = helix_stdx::env::current_working_dir();
+    if !cwd.exists() {
+        cx.editor
+            .set_error("Current working directory does not exist");
+        return;
+    }
+
+    let theme = &cx.editor.theme;
+    let styles = ChangedFileStyles {
+        cwd: cwd.clone(),
+        untracked: theme.get("diff.plus"),
+        modified: theme.get("diff.delta"),
+        conflict: theme.get("diff.delta.conflict"),
+        deleted: theme.get("diff.minus"),
+        renamed: theme.get("diff.delta.moved"),
+    };
+
+    let (picker, injector) = Picker::new(
+        Vec::new(),
+        styles,
+        |cx, file: &ChangedFile, action| {
+            let path = file.0.path();
+            if let Err(err) = cx.editor.open(path, action) {
+                cx.editor
+                    .set_error(format!("Failed to open {}: {}", path.display(), err));
+            }
+        },
+        Some(Box::new(|_editor: &Editor, file: &ChangedFile| {
+            Some((file.0.path().to_path_buf(), None))
+        })),
+    );
+    cx.editor
+        .diff_providers
+        .clone()
+        .for_each_changed_file(cwd, move |change| match change {
+            Ok(change) => injector.push(ChangedFile(change)).is_ok(),
+            Err(err) => {
+                log::error!("failed to list changed files: {err}");
+                true
+            }
+        });
+    cx.push_layer(Box::new(picker));
+}
+
+struct FileResult {
+    path: PathBuf,
+    // 0 indexed
+    line_num: usize,
+}
+
+impl Item for FileResult {
+    // path of the current document, marked in the list
+    type Data = Option<PathBuf>;
+
+    fn format(&self, current_path: &Self::Data) -> Row {
+        let relative_path = get_relative_path(&self.path).to_string_lossy().into_owned();
+        if current_path.as_ref() == Some(&self.path) {
+            format!("{} (*)", relative_path).into()
+        } else {
+            relative_path.into()
+        }
+    }
+}
+
+// mostly copy pasted from helix_term/src/commands.rs
+fn global_search(cx: &mut Context) {
+    let reg = cx.register.unwrap_or('/');
+    let prompt = ui::Prompt::new(
+        "global-search:".into(),
+        Some(reg),
+        ui::completers::none,
+        move |cx: &mut compositor::Context, input: &str, event: PromptEvent| {
+            if event != PromptEvent::Validate || input.is_empty() {
+                return;
+            }
+            cx.editor.registers.last_search_register = reg;
+            if let Some(picker) = global_search_picker(cx.editor, input) {
+                cx.jobs.callback(async move {
+                    let call = move |_: &mut Editor, compositor: &mut Compositor| {
+                        compositor.push(Box::new(picker))
+                    };
+                    Ok(helix_term::job::Callback::EditorCompositor(Box::new(call)))
+                });
+            }
+        },
+    );
+    cx.push_layer(Box::new(prompt));
+}
+
+fn global_search_picker(editor: &mut Editor, pattern: &str) -> Option<Picker> {
+    use grep_regex::RegexMatcherBuilder;
+    use grep_searcher::{sinks, BinaryDetection, SearcherBuilder};
+    use ignore::{DirEntry, WalkBuilder, WalkState};
+
+    let config = editor.config();
+    let matcher = match RegexMatcherBuilder::new()
+        .case_smart(config.search.smart_case)
+        .build(pattern)
+    {
+        Ok(matcher) => matcher,
+        Err(err) => {
+            editor.set_error(format!("invalid regex: {err}"));
+            return None;
+        }
+    };
+    let search_root = helix_stdx::env::current_working_dir();
+    if !search_root.exists() {
+        editor.set_error("Current working directory does not exist");
+        return None;
+    }
+
+    // open buffers are searched instead of their files, to find unsaved edits
+    let documents: Vec<_> = editor
+        .documents()
+        .map(|doc| (doc.path().cloned(), doc.text().to_string()))
+        .collect();
+    let current_path = doc!(editor).path().cloned();
+    let (picker, injector) = Picker::new(
+        Vec::new(),
+        current_path,
+        |cx, result: &FileResult, action| {
+            let doc_id = match cx.editor.open(&result.path, action) {
+                Ok(doc_id) => doc_id,
+                Err(err) => {
+                    cx.editor.set_error(format!(
+                        "Failed to open file '{}': {}",
+                        result.path.display(),
+                        err
+                    ));
+                    return;
+                }
+            };
+            let (view, doc) = (view_mut!(cx.editor), doc_mut!(cx.editor, &doc_id));
+            let text = doc.text().slice(..);
+            let line = result.line_num.min(text.len_lines().saturating_sub(1));
+            let pos = text.line_to_char(line);
+            doc.set_selection(view.id, Selection::point(pos));
+            if action.align_view(view, doc.id()) {
+                align_view(doc, view, Align::Center);
+            }
+        },
+        Some(Box::new(|_editor: &Editor, result: &FileResult| {
+            Some((
+                result.path.clone(),
+                Some((result.line_num, result.line_num)),
+            ))
+        })),
+    );
+
+    let file_picker_config = config.file_picker.clone();
+    let dedup_symlinks = file_picker_config.deduplicate_links;
+    let absolute_root = search_root
+        .canonicalize()
+        .unwrap_or_else(|_| search_root.clone());
+    std::thread::spawn(move || {
+        let searcher = SearcherBuilder::new()
+            .binary_detection(BinaryDetection::quit(b'\x00'))
+            .build();
+
+        WalkBuilder::new(search_root)
+            .hidden(file_picker_config.hidden)
+            .parents(file_picker_config.parents)
+            .ignore(file_picker_config.ignore)
+            .follow_links(file_picker_config.follow_symlinks)
+            .git_ignore(file_picker_config.git_ignore)
+            .git_global(file_picker_config.git_global)
+            .git_exclude(file_picker_config.git_exclude)
+            .max_depth(file_picker_config.max_depth)
+            .filter_entry(move |entry| {
+                picker::filter_picker_entry(entry, &absolute_root, dedup_symlinks)
+            })
+            .add_custom_ignore_filename(helix_loader::config_dir().join("ignore"))
+            .add_custom_ignore_filename(".helix/ignore")
+            .build_parallel()
+            .run(|| {
+                let mut searcher = searcher.clone();
+                let matcher = matcher.clone();
+                let injector = injector.clone();
+                let documents = &documents;
+                Box::new(move |entry: Result<DirEntry, ignore::Error>| -> WalkState {
+                    let Ok(entry) = entry else {
+                        return WalkState::Continue;
+                    };
+                    if !entry.file_type().map_or(false, |ty| ty.is_file()) {
+                        return WalkState::Continue;
+                    }
+
+                    let mut stop = false;
+                    let sink = sinks::UTF8(|line_num, _| {
+                        let result = FileResult {
+                            path: entry.path().to_path_buf(),
+                            line_num: line_num as usize - 1,
+                        };
+                        stop = injector.push(result).is_err();
+                        Ok(!stop)
+                    });
+                    let doc = documents
+                        .iter()
+                        .find(|(path, _)| path.as_deref() == Some(entry.path()));
+                    let result = match doc {
+                        Some((_, text)) => searcher.search_slice(&matcher, text.as_bytes(), sink),
+                        None => searcher.search_path(&matcher, entry.path(), sink),
+                    };
+                    if let Err(err) = result {
+                        log::error!("Global search error: {}, {}", entry.path().display(), err);
+                    }
+                    if stop {
+                        WalkState::Quit
+                    } else {
+                        WalkState::Continue
+                    }
+                })
+            });
+    });
+    Some(picker)
+}
+
+// mostly copy pasted from helix_term/src/commands.rs
+fn command_palette(cx: &mut Context) {
+    let register = cx.register;
+    let count = cx.count;
+    let mode = cx.editor.mode();
+    let keymap: ReverseKeymap = KEYMAP.with(|keymap| {
+        keymap
+            .borrow()
+            .get(&mode)
+            .map(KeyTrie::reverse_map)
+            .unwrap_or_default()
+    });
+
+    let commands = MappableCommand::STATIC_COMMAND_LIST
+        .iter()
+        .cloned()
+        .map(|mut command| {
+            replace_command(&mut command);
+            command
+        })
+        .chain(
+            TYPABLE_COMMAND_LIST
+                .iter()
+                .map(|command| MappableCommand::Typable {
+                    name: command.name.to_owned(),
+                    doc: command.doc.to_owned(),
+                    args: Vec::new(),
+                }),
+        )
+        .collect();
+
+    let (picker, _) = Picker::new(
+        commands,
+        keymap,
+        move |cx, command: &MappableCommand, _action| {
+            cx.register = register;
+            cx.count = count;
+            let focus = view!(cx.editor).id;
+
+            command.execute(cx);
+
+            if cx.editor.tree.contains(focus) {
+                let config = cx.editor.config();
+                let mode = cx.editor.mode();
+                let view = view_mut!(cx.editor, focus);
+                let doc = doc_mut!(cx.editor, &view.doc);
+
+                view.ensure_cursor_in_view(doc, config.scrolloff);
+                if mode != Mode::Insert {
+                    doc.append_changes_to_history(view);
+                }
+            }
+        },
+        None,
+    );
+    cx.push_layer(Box::new(picker));
+}
+
+fn last_picker(cx: &mut Context) {
+    cx.callback.push(Box::new(|compositor, cx| {
+        match picker::take_last_picker() {
+            Some(picker) => compositor.push(picker),
+            None => cx.editor.set_error("no last picker"),
+        }
+    }));
+}
+
+/// helix's `:` prompt, wrapped so that it can be told apart from the other
+/// prompts when the GUI takes its own commands out of it.
+pub struct CommandPrompt(Box<ui::Prompt>);
+
+impl CommandPrompt {
+    pub fn line(&self) -> &str {
+        self.0.line()
+    }
+}
+
+impl Component for CommandPrompt {
+    fn handle_event(&mut self, event: &Event, cx: &mut compositor::Context) -> EventResult {
+        self.0.handle_event(event, cx)
+    }
+
+    fn should_update(&self) -> bool {
+        self.0.should_update()
+    }
+
+    fn render(
+        &mut self,
+        area: Rect,
+        surface: &mut tui::buffer::Buffer,
+        cx: &mut compositor::Context,
+    ) {
+        self.0.render(area, surface, cx)
+    }
+
+    fn cursor(&self, area: Rect, editor: &Editor) -> (Option<Position>, CursorKind) {
+        self.0.cursor(area, editor)
+    }
+
+    fn required_size(&mut self, viewport: (u16, u16)) -> Option<(u16, u16)> {
+        self.0.required_size(viewport)
+    }
+}
+
+fn command_mode(cx: &mut Context) {
+    MappableCommand::command_mode.execute(cx);
+    // runs after the callback pushing helix's prompt
+    cx.callback.push(Box::new(|compositor, _cx| {
+        let Some(layer) = compositor.pop() else {
+            return;
+        };
+        if layer.type_name() != std::any::type_name::<ui::Prompt>() {
+            compositor.push(layer);
+            return;
+        }
+        if let Ok(prompt) = layer.as_boxed_any().downcast::<ui::Prompt>() {
+            compositor.push(Box::new(CommandPrompt(prompt)));
+        }
+    }));
+}
+
+fn to_locations(definitions: Option<lsp::GotoDefinitionResponse>) -> Vec<lsp::Location> {
+    match definitions {
+        Some(lsp::GotoDefinitionResponse::Scalar(location)) => vec![location],
+        Some(lsp::GotoDefinitionResponse::Array(locations)) => locations,
+        Some(lsp::GotoDefinitionResponse::Link(locations)) => locations
+            .into_iter()
+            .map(|location_link| lsp::Location {
+                uri: location_link.target_uri,
+                range: location_link.target_range,
+            })
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// jumps to a single location, a picker lists several
+fn goto_locations(
+    editor: &mut Editor,
+    compositor: &mut Compositor,
+    locations: Vec<lsp::Location>,
+    offset_encoding: OffsetEncoding,
+    not_found: &'static str,
+) {
+    match locations.as_slice() {
+        [] => editor.set_error(not_found),
+        [location] => jump_to_location(editor, location, offset_encoding, Action::Replace),
+        _ => {
+            let cwd = helix_stdx::env::current_working_dir();
+            let (picker, _) = Picker::new(
+                locations,
+                cwd,
+                move |cx, location: &lsp::Location, action| {
+                    jump_to_location(cx.editor, location, offset_encoding, action)
+                },
+                Some(Box::new(|_editor: &Editor, location: &lsp::Location| {
+                    location_to_file_location(location)
+                })),
+            );
+            compositor.push(Box::new(picker));
+        }
+    }
+}
+
+// mostly copy pasted from helix_term/src/commands/lsp.rs
+fn goto_single<F>(
+    cx: &mut Context,
+    feature: LanguageServerFeature,
+    not_found: &'static str,
+    request: impl Fn(&helix_lsp::Client, lsp::Position, lsp::TextDocumentIdentifier) -> Option<F>,
+) where
+    F: std::future::Future<Output = helix_lsp::Result<serde_json::Value>> + Send + 'static,
+{
+    let (view, doc) = current!(cx.editor);
+    let Some(language_server) = doc.language_servers_with_feature(feature).next() else {
+        cx.editor
+            .set_error(format!("No configured language server supports {feature}"));
+        return;
+    };
+    let offset_encoding = language_server.offset_encoding();
+    let pos = doc.position(view.id, offset_encoding);
+    let Some(future) = request(language_server, pos, doc.identifier()) else {
+        return;
+    };
+    cx.callback(
+        future,
+        move |editor, compositor, response: Option<lsp::GotoDefinitionResponse>| {
+            goto_locations(
+                editor,
+                compositor,
+                to_locations(response),
+                offset_encoding,
+                not_found,
+            );
+        },
+    );
+}
+
+fn goto_definition(cx: &mut Context) {
+    goto_single(
+        cx,
+        LanguageServerFeature::GotoDefinition,
+        "No definition found.",
+        |ls, pos, id| ls.goto_definition(id, pos, None),
+    );
+}
+
+fn goto_declaration(cx: &mut Context) {
+    goto_single(
+        cx,
+        LanguageServerFeature::GotoDeclaration,
+        "No declaration found.",
+        |ls, pos, id| ls.goto_declaration(id, pos, None),
+    );
+}
+
+fn goto_type_definition(cx: &mut Context) {
+    goto_single(
+        cx,
+        LanguageServerFeature::GotoTypeDefinition,
+        "No type definition found.",
+        |ls, pos, id| ls.goto_type_definition(id, pos, None),
+    );
+}
+
+fn goto_implementation(cx: &mut Context) {
+    goto_single(
+        cx,
+        LanguageServerFeature::GotoImplementation,
+        "No implementation found.",
+        |ls, pos, id| ls.goto_implementation(id, pos, None),
+    );
+}
+
+fn goto_reference(cx: &mut Context) {
+    let include_declaration = cx.editor.config().lsp.goto_reference_include_declaration;
+    let (view, doc) = current!(cx.editor);
+    let Some(language_server) = doc
+        .language_servers_with_feature(LanguageServerFeature::GotoReference)
+        .next()
+    else {
+        cx.editor
+            .set_error("No configured language server supports goto-reference");
+        return;
+    };
+    let offset_encoding = language_server.offset_encoding();
+    let pos = doc.position(view.id, offset_encoding);
+    let Some(future) =
+        language_server.goto_reference(doc.identifier(), pos, include_declaration, None)
+    else {
+        return;
+    };
+    cx.callback(
+        future,
+        move |editor, compositor, response: Option<Vec<lsp::Location>>| {
+            goto_locations(
+                editor,
+                compositor,
+                response.unwrap_or_default(),
+                offset_encoding,
+                "No references found.",
+            );
+        },
+    );
+}
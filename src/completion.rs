@@ -0,0 +1,905 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use gpui::prelude::FluentBuilder;
+use gpui::*;
+use helix_core::chars::char_is_word;
+use helix_core::syntax::LanguageServerFeature;
+use helix_event::{
+    cancelable_future, cancelation, register_hook, send_blocking, AsyncHook, CancelRx, CancelTx,
+};
+use helix_lsp::{lsp, util, LanguageServerId, OffsetEncoding};
+use helix_stdx::rope::RopeSliceExt;
+use helix_term::commands::{self, MappableCommand};
+use helix_term::compositor::{self, Component, Compositor, EventResult};
+use helix_term::config::Config;
+use helix_term::events::{OnModeSwitch, PostCommand, PostInsertChar};
+use helix_term::job::{dispatch, dispatch_blocking};
+use helix_view::document::Mode;
+use helix_view::handlers::completion::CompletionEvent;
+use helix_view::handlers::Handlers;
+use helix_view::input::Event;
+use helix_view::{ctrl, current, current_ref, key, shift, Document, DocumentId, Editor, ViewId};
+use nucleo::pattern::{Atom, AtomKind, CaseMatching};
+use nucleo::Utf32Str;
+use tokio::sync::mpsc::Sender;
+use tokio::time::Instant;
+
+use crate::utils::color_to_hsla;
+
+const MAX_VISIBLE_ITEMS: usize = 12;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum TriggerKind {
+    Auto,
+    TriggerChar,
+    Manual,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Trigger {
+    pos: usize,
+    view: ViewId,
+    doc: DocumentId,
+    kind: TriggerKind,
+}
+
+// mostly copy pasted from helix_term/src/handlers/completion.rs
+pub struct CompletionHandler {
+    /// currently active trigger which will cause a
+    /// completion request after the timeout
+    trigger: Option<Trigger>,
+    /// A handle for currently active completion request.
+    /// Dropping it aborts the request.
+    request: Option<CancelTx>,
+    config: Arc<ArcSwap<Config>>,
+}
+
+impl CompletionHandler {
+    pub fn new(config: Arc<ArcSwap<Config>>) -> Self {
+        Self {
+            trigger: None,
+            request: None,
+            config,
+        }
+    }
+}
+
+impl AsyncHook for CompletionHandler {
+    type Event = CompletionEvent;
+
+    fn handle_event(
+        &mut self,
+        event: Self::Event,
+        _old_timeout: Option<Instant>,
+    ) -> Option<Instant> {
+        match event {
+            CompletionEvent::AutoTrigger { cursor, doc, view } => {
+                if self.trigger.is_none() && self.request.is_none() {
+                    self.trigger = Some(Trigger {
+                        pos: cursor,
+                        view,
+                        doc,
+                        kind: TriggerKind::Auto,
+                    });
+                }
+            }
+            CompletionEvent::TriggerChar { cursor, doc, view } => {
+                // immediately request completions and drop all auto completion requests
+                self.request = None;
+                self.trigger = Some(Trigger {
+                    pos: cursor,
+                    view,
+                    doc,
+                    kind: TriggerKind::TriggerChar,
+                });
+            }
+            CompletionEvent::ManualTrigger { cursor, doc, view } => {
+                self.request = None;
+                self.trigger = Some(Trigger {
+                    pos: cursor,
+                    view,
+                    doc,
+                    kind: TriggerKind::Manual,
+                });
+                // stop debouncing immediately and request the completion
+                self.finish_debounce();
+                return None;
+            }
+            CompletionEvent::Cancel => {
+                self.trigger = None;
+                self.request = None;
+            }
+            CompletionEvent::DeleteText { cursor } => {
+                // if we deleted the original trigger, abort the completion
+                if matches!(self.trigger, Some(Trigger { pos, .. }) if cursor < pos) {
+                    self.trigger = None;
+                    self.request = None;
+                }
+            }
+        }
+        self.trigger.map(|trigger| {
+            // if the current request was closed forget about it
+            // otherwise immediately restart the completion request
+            let cancel = self.request.take().map_or(false, |req| !req.is_closed());
+            let timeout = if trigger.kind == TriggerKind::Auto && !cancel {
+                self.config.load().editor.completion_timeout
+            } else {
+                // we want almost instant completions for trigger chars
+                // and restarting completion requests
+                Duration::from_millis(5)
+            };
+            Instant::now() + timeout
+        })
+    }
+
+    fn finish_debounce(&mut self) {
+        let trigger = self.trigger.take().expect("debounce always has a trigger");
+        let (tx, rx) = cancelation();
+        self.request = Some(tx);
+        dispatch_blocking(move |editor, compositor| {
+            request_completion(trigger, rx, editor, compositor)
+        });
+    }
+}
+
+fn request_completion(
+    mut trigger: Trigger,
+    cancel: CancelRx,
+    editor: &mut Editor,
+    compositor: &mut Compositor,
+) {
+    let (view, doc) = current_ref!(editor);
+
+    if compositor.find::<Completion>().is_some() || editor.mode != Mode::Insert {
+        return;
+    }
+
+    let text = doc.text();
+    let cursor = doc.selection(view.id).primary().cursor(text.slice(..));
+    if trigger.view != view.id || trigger.doc != doc.id() || cursor < trigger.pos {
+        return;
+    }
+    // the language server resolves the completion to the identifier anyway,
+    // sending the latest position gives better results for incomplete lists
+    trigger.pos = cursor;
+    let trigger_text = text.slice(..cursor);
+
+    let mut seen_language_servers = HashSet::new();
+    let mut futures: FuturesUnordered<_> = doc
+        .language_servers_with_feature(LanguageServerFeature::Completion)
+        .filter(|ls| seen_language_servers.insert(ls.id()))
+        .map(|ls| {
+            let language_server_id = ls.id();
+            let offset_encoding = ls.offset_encoding();
+            let pos = util::pos_to_lsp_pos(text, cursor, offset_encoding);
+            let doc_id = doc.identifier();
+            let context = if trigger.kind == TriggerKind::Manual {
+                lsp::CompletionContext {
+                    trigger_kind: lsp::CompletionTriggerKind::INVOKED,
+                    trigger_character: None,
+                }
+            } else {
+                let trigger_char =
+                    ls.capabilities()
+                        .completion_provider
+                        .as_ref()
+                        .and_then(|provider| {
+                            provider
+                                .trigger_characters
+                                .as_deref()?
+                                .iter()
+                                .find(|&trigger| trigger_text.ends_with(trigger))
+                        });
+                lsp::CompletionContext {
+                    trigger_kind: if trigger_char.is_some() {
+                        lsp::CompletionTriggerKind::TRIGGER_CHARACTER
+                    } else {
+                        lsp::CompletionTriggerKind::INVOKED
+                    },
+                    trigger_character: trigger_char.cloned(),
+                }
+            };
+
+            let completion_response = ls.completion(doc_id, pos, None, context).unwrap();
+            async move {
+                let json = completion_response.await?;
+                let response: Option<lsp::CompletionResponse> = serde_json::from_value(json)?;
+                let items = match response {
+                    Some(lsp::CompletionResponse::Array(items)) => items,
+                    Some(lsp::CompletionResponse::List(lsp::CompletionList {
+                        is_incomplete: _,
+                        items,
+                    })) => items,
+                    None => Vec::new(),
+                }
+                .into_iter()
+                .map(|item| CompletionItem {
+                    item,
+                    provider: language_server_id,
+                    offset_encoding,
+                    resolved: false,
+                })
+                .collect::<Vec<_>>();
+                anyhow::Ok(items)
+            }
+        })
+        .collect();
+
+    let future = async move {
+        let mut items = Vec::new();
+        while let Some(lsp_items) = futures.next().await {
+            match lsp_items {
+                Ok(mut lsp_items) => items.append(&mut lsp_items),
+                Err(err) => {
+                    log::debug!("completion request failed: {err:?}");
+                }
+            };
+        }
+        items
+    };
+
+    tokio::spawn(async move {
+        let items = cancelable_future(future, cancel).await.unwrap_or_default();
+        if items.is_empty() {
+            return;
+        }
+        dispatch(move |editor, compositor| show_completion(editor, compositor, items, trigger))
+            .await
+    });
+}
+
+fn show_completion(
+    editor: &mut Editor,
+    compositor: &mut Compositor,
+    items: Vec<CompletionItem>,
+    trigger: Trigger,
+) {
+    let (view, doc) = current_ref!(editor);
+    // check if the completion request is stale, the user could have
+    // switched document/view or left insert mode in the meantime
+    if editor.mode != Mode::Insert || view.id != trigger.view || doc.id() != trigger.doc {
+        return;
+    }
+    if compositor.find::<Completion>().is_some() {
+        return;
+    }
+
+    let completion = Completion::new(editor, items, trigger.view);
+    if completion.is_empty() {
+        // skip if we got no completion results
+        return;
+    }
+    compositor.push(Box::new(completion));
+}
+
+fn trigger_auto_completion(tx: &Sender<CompletionEvent>, editor: &Editor, trigger_char_only: bool) {
+    let config = editor.config.load();
+    if !config.auto_completion {
+        return;
+    }
+    let (view, doc) = current_ref!(editor);
+    let text = doc.text().slice(..);
+    let cursor = doc.selection(view.id).primary().cursor(text);
+    let text = text.slice(..cursor);
+
+    let is_trigger_char = doc
+        .language_servers_with_feature(LanguageServerFeature::Completion)
+        .any(|ls| {
+            matches!(&ls.capabilities().completion_provider, Some(lsp::CompletionOptions {
+                        trigger_characters: Some(triggers),
+                        ..
+                    }) if triggers.iter().any(|trigger| text.ends_with(trigger)))
+        });
+    if is_trigger_char {
+        send_blocking(
+            tx,
+            CompletionEvent::TriggerChar {
+                cursor,
+                doc: doc.id(),
+                view: view.id,
+            },
+        );
+        return;
+    }
+
+    let trigger_len = config.completion_trigger_len as usize;
+    let is_auto_trigger = !trigger_char_only
+        && text.chars_at(cursor).reversed().take(trigger_len).count() == trigger_len
+        && text
+            .chars_at(cursor)
+            .reversed()
+            .take(trigger_len)
+            .all(char_is_word);
+
+    if is_auto_trigger {
+        send_blocking(
+            tx,
+            CompletionEvent::AutoTrigger {
+                cursor,
+                doc: doc.id(),
+                view: view.id,
+            },
+        );
+    }
+}
+
+fn update_completions(cx: &mut commands::Context, c: Option<char>) {
+    cx.callback.push(Box::new(move |compositor, cx| {
+        let Some(completion) = compositor.find::<Completion>() else {
+            if c.is_some() {
+                trigger_auto_completion(&cx.editor.handlers.completions, cx.editor, false);
+            }
+            return;
+        };
+        completion.update_filter(cx.editor);
+        if completion.is_empty() {
+            compositor.remove(Completion::ID);
+            // clearing completions might mean we want to immediately
+            // rerequest them (usually this occurs if typing a trigger char)
+            if c.is_some() {
+                trigger_auto_completion(&cx.editor.handlers.completions, cx.editor, false);
+            }
+        }
+    }))
+}
+
+fn clear_completions(cx: &mut commands::Context) {
+    cx.callback.push(Box::new(|compositor, _cx| {
+        compositor.remove(Completion::ID);
+    }))
+}
+
+fn completion_post_command_hook(
+    tx: &Sender<CompletionEvent>,
+    PostCommand { command, cx }: &mut PostCommand<'_, '_>,
+) -> anyhow::Result<()> {
+    if cx.editor.mode != Mode::Insert {
+        return Ok(());
+    }
+    match command {
+        MappableCommand::Static {
+            name: "delete_char_backward",
+            ..
+        } => {
+            let (view, doc) = current!(cx.editor);
+            let cursor = doc
+                .selection(view.id)
+                .primary()
+                .cursor(doc.text().slice(..));
+            send_blocking(tx, CompletionEvent::DeleteText { cursor });
+            update_completions(cx, None);
+        }
+        MappableCommand::Static {
+            name: "delete_word_forward" | "delete_char_forward",
+            ..
+        } => {
+            let (view, doc) = current!(cx.editor);
+            let cursor = doc
+                .selection(view.id)
+                .primary()
+                .cursor(doc.text().slice(..));
+            send_blocking(tx, CompletionEvent::DeleteText { cursor });
+        }
+        // some commands are handled elsewhere and we don't want to cancel in that case
+        MappableCommand::Static {
+            name: "completion" | "insert_mode" | "append_mode",
+            ..
+        } => (),
+        _ => {
+            send_blocking(tx, CompletionEvent::Cancel);
+            clear_completions(cx);
+        }
+    }
+    Ok(())
+}
+
+pub fn register_hooks(handlers: &Handlers) {
+    let tx = handlers.completions.clone();
+    register_hook!(move |event: &mut PostCommand<'_, '_>| completion_post_command_hook(&tx, event));
+
+    let tx = handlers.completions.clone();
+    register_hook!(move |event: &mut OnModeSwitch<'_, '_>| {
+        if event.old_mode == Mode::Insert {
+            send_blocking(&tx, CompletionEvent::Cancel);
+            clear_completions(event.cx);
+        } else if event.new_mode == Mode::Insert {
+            trigger_auto_completion(&tx, event.cx.editor, false)
+        }
+        Ok(())
+    });
+
+    register_hook!(move |event: &mut PostInsertChar<'_, '_>| {
+        update_completions(event.cx, Some(event.c));
+        Ok(())
+    });
+}
+
+#[derive(Debug, Clone)]
+pub struct CompletionItem {
+    item: lsp::CompletionItem,
+    provider: LanguageServerId,
+    offset_encoding: OffsetEncoding,
+    resolved: bool,
+}
+
+impl CompletionItem {
+    fn filter_text(&self) -> &str {
+        self.item
+            .filter_text
+            .as_ref()
+            .unwrap_or(&self.item.label)
+            .as_str()
+    }
+
+    fn kind(&self) -> &'static str {
+        match self.item.kind {
+            Some(lsp::CompletionItemKind::TEXT) => "text",
+            Some(lsp::CompletionItemKind::METHOD) => "method",
+            Some(lsp::CompletionItemKind::FUNCTION) => "function",
+            Some(lsp::CompletionItemKind::CONSTRUCTOR) => "constructor",
+            Some(lsp::CompletionItemKind::FIELD) => "field",
+            Some(lsp::CompletionItemKind::VARIABLE) => "variable",
+            Some(lsp::CompletionItemKind::CLASS) => "class",
+            Some(lsp::CompletionItemKind::INTERFACE) => "interface",
+            Some(lsp::CompletionItemKind::MODULE) => "module",
+            Some(lsp::CompletionItemKind::PROPERTY) => "property",
+            Some(lsp::CompletionItemKind::UNIT) => "unit",
+            Some(lsp::CompletionItemKind::VALUE) => "value",
+            Some(lsp::CompletionItemKind::ENUM) => "enum",
+            Some(lsp::CompletionItemKind::KEYWORD) => "keyword",
+            Some(lsp::CompletionItemKind::SNIPPET) => "snippet",
+            Some(lsp::CompletionItemKind::COLOR) => "color",
+            Some(lsp::CompletionItemKind::FILE) => "file",
+            Some(lsp::CompletionItemKind::REFERENCE) => "reference",
+            Some(lsp::CompletionItemKind::FOLDER) => "folder",
+            Some(lsp::CompletionItemKind::ENUM_MEMBER) => "enum_member",
+            Some(lsp::CompletionItemKind::CONSTANT) => "constant",
+            Some(lsp::CompletionItemKind::STRUCT) => "struct",
+            Some(lsp::CompletionItemKind::EVENT) => "event",
+            Some(lsp::CompletionItemKind::OPERATOR) => "operator",
+            Some(lsp::CompletionItemKind::TYPE_PARAMETER) => "type_param",
+            Some(kind) => {
+                log::error!("Received unknown completion item kind: {:?}", kind);
+                ""
+            }
+            None => "",
+        }
+    }
+
+    fn documentation(&self) -> Option<String> {
+        let detail = self.item.detail.as_deref().filter(|s| !s.is_empty());
+        let documentation = match &self.item.documentation {
+            Some(lsp::Documentation::String(contents))
+            | Some(lsp::Documentation::MarkupContent(lsp::MarkupContent {
+                value: contents, ..
+            })) => Some(contents.as_str()).filter(|s| !s.is_empty()),
+            None => None,
+        };
+        match (detail, documentation) {
+            (Some(detail), Some(doc)) => Some(format!("{}\n\n{}", detail, doc)),
+            (Some(text), None) | (None, Some(text)) => Some(text.to_string()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Completion menu, lives in the compositor so that it gets key events
+/// before the editor view. It is rendered natively by the document view.
+pub struct Completion {
+    items: Vec<CompletionItem>,
+    /// indices into `items`, best match first
+    matches: Vec<usize>,
+    cursor: usize,
+    view_id: ViewId,
+    /// start of the word that is being completed
+    start_offset: usize,
+}
+
+impl Completion {
+    pub const ID: &'static str = "completion";
+
+    fn new(editor: &Editor, mut items: Vec<CompletionItem>, view_id: ViewId) -> Self {
+        // sort by label so that items with equal score keep a stable order
+        items.sort_by(|a, b| {
+            let a = a.item.sort_text.as_ref().unwrap_or(&a.item.label);
+            let b = b.item.sort_text.as_ref().unwrap_or(&b.item.label);
+            a.cmp(b)
+        });
+
+        let (view, doc) = current_ref!(editor);
+        let text = doc.text().slice(..);
+        let cursor = doc.selection(view.id).primary().cursor(text);
+        let offset = text
+            .chars_at(cursor)
+            .reversed()
+            .take_while(|ch| char_is_word(*ch))
+            .count();
+
+        let mut completion = Self {
+            items,
+            matches: Vec::new(),
+            cursor: 0,
+            view_id,
+            start_offset: cursor.saturating_sub(offset),
+        };
+        completion.update_filter(editor);
+        completion
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    /// Rescores the items against the text typed since the completion was opened.
+    pub fn update_filter(&mut self, editor: &Editor) {
+        let (view, doc) = current_ref!(editor);
+        let text = doc.text().slice(..);
+        let cursor = doc.selection(view.id).primary().cursor(text);
+        if view.id != self.view_id || cursor < self.start_offset {
+            self.matches.clear();
+            return;
+        }
+        let pattern = text.slice(self.start_offset..cursor).to_string();
+
+        let mut matcher = helix_core::fuzzy::MATCHER.lock();
+        matcher.config = nucleo::Config::DEFAULT;
+        let atom = Atom::new(&pattern, CaseMatching::Ignore, AtomKind::Fuzzy, false);
+        let mut buf = Vec::new();
+        let mut matches: Vec<_> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, item)| {
+                let haystack = Utf32Str::new(item.filter_text(), &mut buf);
+                atom.score(haystack, &mut matcher).map(|score| (idx, score))
+            })
+            .collect();
+        // sort_by is stable, equal scores keep the language server order
+        matches.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        self.matches = matches.into_iter().map(|(idx, _)| idx).collect();
+        self.cursor = 0;
+    }
+
+    fn selected(&self) -> Option<&CompletionItem> {
+        self.matches.get(self.cursor).map(|idx| &self.items[*idx])
+    }
+
+    fn move_up(&mut self) {
+        let len = self.matches.len();
+        self.cursor = (self.cursor + len.saturating_sub(1)) % len.max(1);
+    }
+
+    fn move_down(&mut self) {
+        let len = self.matches.len();
+        self.cursor = (self.cursor + 1) % len.max(1);
+    }
+
+    /// Requests `completionItem/resolve` for the selected item,
+    /// documentation is often only sent on demand.
+    fn resolve_selected(&mut self, editor: &Editor) {
+        let Some(idx) = self.matches.get(self.cursor).copied() else {
+            return;
+        };
+        let item = &mut self.items[idx];
+        if item.resolved {
+            return;
+        }
+        item.resolved = true;
+        let Some(language_server) = editor.language_server_by_id(item.provider) else {
+            return;
+        };
+        let Some(future) = language_server.resolve_completion_item(item.item.clone()) else {
+            return;
+        };
+        let label = item.item.label.clone();
+        tokio::spawn(async move {
+            let resolved = match future.await {
+                Ok(json) => serde_json::from_value::<lsp::CompletionItem>(json),
+                Err(err) => {
+                    log::error!("failed to resolve completion item {}: {}", label, err);
+                    return;
+                }
+            };
+            let Ok(resolved) = resolved else {
+                return;
+            };
+            dispatch(move |_editor, compositor| {
+                if let Some(completion) = compositor.find::<Completion>() {
+                    if let Some(item) = completion.items.get_mut(idx) {
+                        if item.item.label == resolved.label {
+                            item.item = resolved;
+                        }
+                    }
+                }
+            })
+            .await
+        });
+    }
+
+    fn apply(&self, editor: &mut Editor, item: &CompletionItem) {
+        let (view, doc) = current!(editor);
+        let transaction = item_to_transaction(doc, view.id, &item.item, item.offset_encoding);
+        doc.apply(&transaction, view.id);
+
+        // apply additional edits, mostly used to auto import unqualified types
+        if let Some(additional_edits) = &item.item.additional_text_edits {
+            if !additional_edits.is_empty() {
+                let transaction = util::generate_transaction_from_edits(
+                    doc.text(),
+                    additional_edits.clone(),
+                    item.offset_encoding,
+                );
+                doc.apply(&transaction, view.id);
+            }
+        }
+    }
+
+    /// Snapshot of the menu for rendering.
+    pub fn menu(&self, editor: &Editor) -> CompletionMenu {
+        let theme = &editor.theme;
+        let menu_style = theme.get("ui.menu");
+        let selected_style = theme.get("ui.menu.selected");
+        let kind_style = theme.get("ui.text.inactive");
+        let popup_style = theme.get("ui.popup");
+        let text_style = theme.get("ui.text");
+
+        let fg = menu_style
+            .fg
+            .or(text_style.fg)
+            .and_then(color_to_hsla)
+            .unwrap_or(white());
+        let bg = menu_style
+            .bg
+            .or(popup_style.bg)
+            .and_then(color_to_hsla)
+            .unwrap_or(black());
+
+        let first = self
+            .cursor
+            .saturating_sub(MAX_VISIBLE_ITEMS - 1)
+            .min(self.matches.len().saturating_sub(MAX_VISIBLE_ITEMS));
+        let items = self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(MAX_VISIBLE_ITEMS)
+            .map(|(pos, idx)| {
+                let item = &self.items[*idx];
+                CompletionMenuItem {
+                    label: item.item.label.clone().into(),
+                    kind: item.kind().into(),
+                    selected: pos == self.cursor,
+                }
+            })
+            .collect();
+
+        CompletionMenu {
+            view_id: self.view_id,
+            start_offset: self.start_offset,
+            items,
+            documentation: self.selected().and_then(|item| item.documentation()),
+            fg,
+            bg,
+            selected_fg: selected_style.fg.and_then(color_to_hsla).unwrap_or(fg),
+            selected_bg: selected_style.bg.and_then(color_to_hsla).unwrap_or(bg),
+            kind_fg: kind_style.fg.and_then(color_to_hsla).unwrap_or(fg),
+            popup_bg: popup_style.bg.and_then(color_to_hsla).unwrap_or(bg),
+        }
+    }
+}
+
+// copy pasted from helix_term/src/ui/completion.rs
+fn item_to_transaction(
+    doc: &Document,
+    view_id: ViewId,
+    item: &lsp::CompletionItem,
+    offset_encoding: OffsetEncoding,
+) -> helix_core::Transaction {
+    use helix_core::Transaction;
+    use helix_lsp::snippet;
+
+    let replace_mode = false;
+    let selection = doc.selection(view_id);
+    let text = doc.text().slice(..);
+    let primary_cursor = selection.primary().cursor(text);
+
+    let (edit_offset, new_text) = if let Some(edit) = &item.text_edit {
+        let edit = match edit {
+            lsp::CompletionTextEdit::Edit(edit) => edit.clone(),
+            lsp::CompletionTextEdit::InsertAndReplace(item) => {
+                let range = if replace_mode {
+                    item.replace
+                } else {
+                    item.insert
+                };
+                lsp::TextEdit::new(range, item.new_text.clone())
+            }
+        };
+
+        let Some(range) = util::lsp_range_to_range(doc.text(), edit.range, offset_encoding) else {
+            return Transaction::new(doc.text());
+        };
+
+        let start_offset = range.anchor as i128 - primary_cursor as i128;
+        let end_offset = range.head as i128 - primary_cursor as i128;
+
+        (Some((start_offset, end_offset)), edit.new_text)
+    } else {
+        let new_text = item
+            .insert_text
+            .clone()
+            .unwrap_or_else(|| item.label.clone());
+        (None, new_text)
+    };
+
+    if matches!(item.kind, Some(lsp::CompletionItemKind::SNIPPET))
+        || matches!(
+            item.insert_text_format,
+            Some(lsp::InsertTextFormat::SNIPPET)
+        )
+    {
+        match snippet::parse(&new_text) {
+            Ok(snippet) => util::generate_transaction_from_snippet(
+                doc.text(),
+                selection,
+                edit_offset,
+                replace_mode,
+                snippet,
+                doc.line_ending.as_str(),
+                true,
+                doc.tab_width(),
+                doc.indent_width(),
+            ),
+            Err(err) => {
+                log::error!(
+                    "Failed to parse snippet: {:?}, remaining output: {}",
+                    &new_text,
+                    err
+                );
+                Transaction::new(doc.text())
+            }
+        }
+    } else {
+        util::generate_transaction_from_completion_edit(
+            doc.text(),
+            selection,
+            edit_offset,
+            replace_mode,
+            new_text,
+        )
+    }
+}
+
+impl Component for Completion {
+    fn handle_event(&mut self, event: &Event, cx: &mut compositor::Context) -> EventResult {
+        let close_fn: compositor::Callback = Box::new(|compositor, _| {
+            compositor.remove(Self::ID);
+        });
+
+        let Event::Key(event) = event else {
+            return EventResult::Ignored(None);
+        };
+
+        if cx.editor.mode != Mode::Insert || self.is_empty() {
+            return EventResult::Ignored(Some(close_fn));
+        }
+
+        match *event {
+            // let the editor view handle escape as well, so that we leave insert mode
+            key!(Esc) => EventResult::Ignored(Some(close_fn)),
+            shift!(Tab) | key!(Up) | ctrl!('p') => {
+                self.move_up();
+                self.resolve_selected(cx.editor);
+                EventResult::Consumed(None)
+            }
+            key!(Tab) | key!(Down) | ctrl!('n') => {
+                self.move_down();
+                self.resolve_selected(cx.editor);
+                EventResult::Consumed(None)
+            }
+            key!(Enter) => {
+                if let Some(item) = self.selected() {
+                    self.apply(cx.editor, item);
+                }
+                EventResult::Consumed(Some(close_fn))
+            }
+            _ => EventResult::Ignored(None),
+        }
+    }
+
+    fn render(
+        &mut self,
+        _area: helix_view::graphics::Rect,
+        _frame: &mut tui::buffer::Buffer,
+        _ctx: &mut compositor::Context,
+    ) {
+        // rendered by the document view
+    }
+
+    fn id(&self) -> Option<&'static str> {
+        Some(Self::ID)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CompletionMenuItem {
+    label: SharedString,
+    kind: SharedString,
+    selected: bool,
+}
+
+#[derive(Debug, Clone, IntoElement)]
+pub struct CompletionMenu {
+    pub view_id: ViewId,
+    /// char index the menu is anchored at
+    pub start_offset: usize,
+    items: Vec<CompletionMenuItem>,
+    documentation: Option<String>,
+    fg: Hsla,
+    bg: Hsla,
+    selected_fg: Hsla,
+    selected_bg: Hsla,
+    kind_fg: Hsla,
+    popup_bg: Hsla,
+}
+
+impl RenderOnce for CompletionMenu {
+    fn render(self, cx: &mut WindowContext) -> impl IntoElement {
+        let font = cx.global::<crate::FontSettings>().fixed_font.clone();
+        let style = cx.global::<crate::FontSettings>().ui_text_style();
+        let items = self.items.into_iter().map(|item| {
+            let (fg, bg) = if item.selected {
+                (self.selected_fg, self.selected_bg)
+            } else {
+                (self.fg, self.bg)
+            };
+            div()
+                .flex()
+                .flex_row()
+                .justify_between()
+                .gap_4()
+                .px_1()
+                .text_color(fg)
+                .bg(bg)
+                .child(item.label)
+                .child(div().text_color(self.kind_fg).child(item.kind))
+        });
+
+        div()
+            .flex()
+            .flex_row()
+            .items_start()
+            .gap_1()
+            .font(font)
+            .text_size(style.font_size)
+            .line_height(style.line_height)
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .min_w(px(200.))
+                    .shadow_sm()
+                    .rounded_sm()
+                    .bg(self.bg)
+                    .children(items),
+            )
+            .when_some(self.documentation, |this, documentation| {
+                this.child(
+                    div()
+                        .max_w(px(400.))
+                        .p_2()
+                        .shadow_sm()
+                        .rounded_sm()
+                        .text_color(self.fg)
+                        .bg(self.popup_bg)
+                        .child(documentation),
+                )
+            })
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use helix_term::config::{Config, ConfigLoadError};
+use serde::Deserialize;
+
+/// Settings of the GUI, the `[gui]` section of helix's `config.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct GuiConfig {
+    /// Monospace font of documents, popups and prompts.
+    pub font: FontConfig,
+    /// Font of the window's own labels, like the title in the top bar.
+    /// Popups and prompts keep the monospace family but take this size.
+    pub ui_font: FontConfig,
+    /// Space between the edges of the window and its content, in pixels.
+    pub padding: f32,
+    pub cursor: CursorConfig,
+    /// Colours of the 16/256 colour palette used by themes with named or indexed
+    /// colours, `#rrggbb` strings replacing xterm's defaults from the first one on.
+    pub palette: Vec<String>,
+    /// Look up keys of normal and select mode by their place on a US QWERTY keyboard
+    /// instead of the character they type, so keymaps keep working with Cyrillic or
+    /// Greek layouts. Insert mode and prompts still get the typed character.
+    /// gpui exposes no scan codes, so the place comes from a fixed table of the
+    /// Russian, Ukrainian, Belarusian and Greek layouts, other layouts are unaffected.
+    pub physical_keys: bool,
+    /// Cyrillic layout `physical-keys` follows for the chars that layouts put on
+    /// different keys.
+    pub cyrillic_layout: CyrillicLayout,
+    /// Start with the zoom level the window was left at.
+    pub remember_zoom: bool,
+}
+
+impl gpui::Global for GuiConfig {}
+
+/// The Ukrainian and Belarusian layouts both have `і`, on S and on B.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CyrillicLayout {
+    /// Also right for Russian, which has no `і`.
+    #[default]
+    Ukrainian,
+    Belarusian,
+}
+
+/// Settings left out keep the defaults of the font they are for.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FontConfig {
+    pub family: Option<String>,
+    /// In pixels.
+    pub size: Option<f32>,
+    /// Height of a line relative to the size.
+    pub line_height: Option<f32>,
+    /// From 100 (thin) to 900 (black), 400 is regular.
+    pub weight: Option<f32>,
+    /// Space added between chars, in pixels. Only documents draw it, the
+    /// rest of the text is laid out by gpui which doesn't support it.
+    pub letter_spacing: Option<f32>,
+    /// OpenType features by tag, `0` turns one off and `1` on, features
+    /// with variants like `cv01` take the variant's number.
+    /// `calt = 0` turns off the ligatures of most coding fonts.
+    pub features: BTreeMap<String, u32>,
+}
+
+impl FontConfig {
+    pub fn font_features(&self) -> gpui::FontFeatures {
+        let features = self
+            .features
+            .iter()
+            .map(|(tag, value)| (tag.clone(), *value))
+            .collect();
+        gpui::FontFeatures(std::sync::Arc::new(features))
+    }
+
+    // drops the features that aren't four letter tags
+    fn validate_features(&mut self) {
+        self.features.retain(|tag, _| {
+            let is_tag = tag.len() == 4 && tag.chars().all(|c| c.is_ascii_graphic());
+            if !is_tag {
+                log::warn!("invalid font feature {:?}, expected a four letter tag", tag);
+            }
+            is_tag
+        });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CursorConfig {
+    /// Blink the cursor of the focused view, it stays on while typing.
+    pub blink: bool,
+    /// How long the cursor stays on and off when blinking, in milliseconds.
+    pub blink_interval: u64,
+    /// Animate the primary cursor moving between positions.
+    pub smooth: bool,
+}
+
+impl Default for CursorConfig {
+    fn default() -> Self {
+        Self {
+            blink: true,
+            blink_interval: 500,
+            smooth: false,
+        }
+    }
+}
+
+/// Loads helix's config and the GUI settings from the global and workspace config files.
+/// helix rejects unknown sections, so `[gui]` is taken out before it gets the files.
+/// A bad `[gui]` section is logged and left at its defaults, helix's config is still used.
+pub fn load_default() -> Result<(Config, GuiConfig), ConfigLoadError> {
+    let (global, global_gui) = split_gui(&helix_loader::config_file())?;
+    let (local, local_gui) = split_gui(&helix_loader::workspace_config_file())?;
+    let config = Config::load(global, local)?;
+
+    let gui = match (global_gui, local_gui) {
+        (Some(global), Some(local)) => Some(helix_loader::merge_toml_values(global, local, 3)),
+        (global, local) => local.or(global),
+    };
+    let mut gui: GuiConfig = match gui.map(toml::Value::try_into) {
+        Some(Ok(gui)) => gui,
+        Some(Err(err)) => {
+            log::error!("bad [gui] config, using the defaults: {}", err);
+            GuiConfig::default()
+        }
+        None => GuiConfig::default(),
+    };
+    gui.font.validate_features();
+    gui.ui_font.validate_features();
+    Ok((config, gui))
+}
+
+/// Zoom steps saved by the last session.
+pub fn load_zoom() -> Option<i32> {
+    let zoom = std::fs::read_to_string(zoom_file()).ok()?;
+    zoom.trim().parse().ok()
+}
+
+pub fn save_zoom(zoom: i32) {
+    let path = zoom_file();
+    let saved = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, zoom.to_string()));
+    if let Err(err) = saved {
+        log::warn!("failed to save zoom level to {}: {}", path.display(), err);
+    }
+}
+
+fn zoom_file() -> PathBuf {
+    helix_loader::cache_dir().join("gui-zoom")
+}
+
+// contents of the config file without the `[gui]` section, and the section
+fn split_gui(
+    path: &Path,
+) -> Result<(Result<String, ConfigLoadError>, Option<toml::Value>), ConfigLoadError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => return Ok((Err(ConfigLoadError::Error(err)), None)),
+    };
+    let mut table: toml::Table = toml::from_str(&contents).map_err(ConfigLoadError::BadConfig)?;
+    let gui = table.remove("gui");
+    Ok((Ok(table.to_string()), gui))
+}
@@ -1,16 +1,27 @@
 use std::borrow::Cow;
+use std::cell::Cell as StdCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use gpui::{prelude::FluentBuilder, *};
 use helix_core::{
-    ropey::RopeSlice,
+    doc_formatter::{DocumentFormatter, GraphemeSource},
+    graphemes::{next_grapheme_boundary, Grapheme},
     syntax::{Highlight, HighlightEvent},
+    visual_offset_from_block, Position,
 };
 use helix_lsp::lsp::{Diagnostic, DiagnosticSeverity, NumberOrString};
 use helix_term::ui::EditorView;
-use helix_view::{graphics::CursorKind, Document, DocumentId, Editor, Theme, View, ViewId};
+use helix_view::{
+    document::Mode, graphics::CursorKind, Document, DocumentId, Editor, Theme, View, ViewId,
+};
 use log::debug;
 
-use crate::utils::color_to_hsla;
+use crate::code_action::{CodeActionMenu, CodeActions};
+use crate::completion::{Completion, CompletionMenu};
+use crate::hover::{Hover, HoverPopup};
+use crate::signature_help::{SignatureHelp, SignatureHelpPopup};
+use crate::utils::{color_to_hsla, text_run};
 use crate::{Core, Input, InputEvent};
 
 pub struct DocumentView {
@@ -20,8 +31,30 @@ pub struct DocumentView {
     style: TextStyle,
     focus: FocusHandle,
     is_focused: bool,
+    // char under the mouse and the pending hover request for it
+    hovered_char: Option<usize>,
+    hover_task: Option<Task<()>>,
+    // cell the mouse was last dragged to while selecting, and the task
+    // scrolling the view while it is above or below it
+    selection_drag: Option<(i32, u16)>,
+    autoscroll_task: Option<Task<()>>,
+    // blink phase of the cursor, the document version, cursor and mode the
+    // blink was last restarted for and the task toggling it
+    cursor_visible: bool,
+    blink_state: Option<(i32, usize, Mode)>,
+    blink_task: Option<Task<()>>,
+    cursor_motion: Rc<StdCell<Option<CursorMotion>>>,
+    // text the input method is composing, drawn at the primary cursor until committed
+    preedit: Option<SharedString>,
 }
 
+/// how long the mouse has to rest on a char before its documentation is requested
+const HOVER_DELAY: Duration = Duration::from_millis(500);
+/// how often the view scrolls by a line while selecting beyond it
+const AUTOSCROLL_INTERVAL: Duration = Duration::from_millis(50);
+/// how long the smooth cursor takes to get to its new position
+const CURSOR_MOTION_DURATION: Duration = Duration::from_millis(80);
+
 impl DocumentView {
     pub fn new(
         core: Model<Core>,
@@ -38,6 +71,15 @@ impl DocumentView {
             style,
             focus: focus.clone(),
             is_focused,
+            hovered_char: None,
+            hover_task: None,
+            selection_drag: None,
+            autoscroll_task: None,
+            cursor_visible: true,
+            blink_state: None,
+            blink_task: None,
+            cursor_motion: Rc::default(),
+            preedit: None,
         }
     }
 
@@ -45,6 +87,10 @@ impl DocumentView {
         self.is_focused = is_focused;
     }
 
+    pub fn set_style(&mut self, style: TextStyle) {
+        self.style = style;
+    }
+
     fn get_diagnostics(&self, cx: &mut ViewContext<Self>) -> Vec<Diagnostic> {
         if !self.is_focused {
             return Vec::new();
@@ -53,7 +99,7 @@ impl DocumentView {
         let core = self.core.read(cx);
         let editor = &core.editor;
 
-        let (cursor_pos, doc_id, first_row) = {
+        let (cursor_pos, doc_id, cursor_line) = {
             let view = editor.tree.get(self.view_id);
             let doc_id = view.doc;
             let document = editor.document(doc_id).unwrap();
@@ -65,13 +111,13 @@ impl DocumentView {
                 .cursor(text.slice(..));
             let cursor_pos = view.screen_coords_at_pos(document, text.slice(..), primary_idx);
 
-            let anchor = view.offset.anchor;
-            let first_row = text.char_to_line(anchor.min(text.len_chars()));
-            (cursor_pos, doc_id, first_row)
+            // screen rows and document lines differ once lines are soft wrapped
+            let cursor_line = text.char_to_line(primary_idx);
+            (cursor_pos, doc_id, cursor_line)
         };
-        let Some(cursor_pos) = cursor_pos else {
+        if cursor_pos.is_none() {
             return Vec::new();
-        };
+        }
 
         let mut diags = Vec::new();
         if let Some(path) = editor.document(doc_id).and_then(|doc| doc.path()).cloned() {
@@ -79,8 +125,7 @@ impl DocumentView {
                 for (diag, _) in diagnostics.iter().filter(|(diag, _)| {
                     let (start_line, end_line) =
                         (diag.range.start.line as usize, diag.range.end.line as usize);
-                    let row = cursor_pos.row + first_row;
-                    start_line <= row && row <= end_line
+                    start_line <= cursor_line && cursor_line <= end_line
                 }) {
                     diags.push(diag.clone());
                 }
@@ -88,6 +133,251 @@ impl DocumentView {
         }
         diags
     }
+
+    fn cell_width(&self, cx: &mut WindowContext) -> Pixels {
+        crate::utils::cell_width(&self.style, cx)
+    }
+
+    /// Top left corner of the cell at `char_idx`, relative to the view.
+    fn char_origin(&self, char_idx: usize, cx: &mut ViewContext<Self>) -> Option<Point<Pixels>> {
+        let (position, gutter_width) = {
+            let editor = &self.core.read(cx).editor;
+            let view = editor.tree.get(self.view_id);
+            let document = editor.document(view.doc)?;
+            let text = document.text().slice(..);
+            let position = view.screen_coords_at_pos(document, text, char_idx)?;
+            (position, view.gutter_offset(document))
+        };
+
+        let cell_width = self.cell_width(cx);
+        let line_height = self.style.line_height_in_pixels(cx.rem_size());
+        Some(point(
+            px(2.) + cell_width * (position.col + gutter_width as usize) as f32,
+            px(1.) + line_height * position.row as f32,
+        ))
+    }
+
+    // char index of the primary cursor and its offset in UTF-16 code units
+    fn primary_cursor(&self, cx: &AppContext) -> Option<(usize, usize)> {
+        let editor = &self.core.read(cx).editor;
+        let view = editor.tree.try_get(self.view_id)?;
+        let document = editor.document(view.doc)?;
+        let text = document.text();
+        let cursor = document.selection(view.id).primary().cursor(text.slice(..));
+        Some((cursor, text.char_to_utf16_cu(cursor)))
+    }
+
+    /// Completion menu and its origin relative to the view, placed right below the completed word.
+    fn completion_menu(
+        &self,
+        cx: &mut ViewContext<Self>,
+    ) -> Option<(Point<Pixels>, CompletionMenu)> {
+        if !self.is_focused {
+            return None;
+        }
+        let menu = self.core.update(cx, |core, _cx| {
+            core.compositor
+                .find::<Completion>()
+                .filter(|completion| !completion.is_empty())
+                .map(|completion| completion.menu(&core.editor))
+        })?;
+        if menu.view_id != self.view_id {
+            return None;
+        }
+
+        let mut origin = self.char_origin(menu.start_offset, cx)?;
+        origin.y += self.style.line_height_in_pixels(cx.rem_size());
+        Some((origin, menu))
+    }
+
+    /// Code action menu and its origin relative to the view, placed right below the cursor.
+    fn code_action_menu(
+        &self,
+        cx: &mut ViewContext<Self>,
+    ) -> Option<(Point<Pixels>, CodeActionMenu)> {
+        if !self.is_focused {
+            return None;
+        }
+        let menu = self.core.update(cx, |core, _cx| {
+            core.compositor
+                .find::<CodeActions>()
+                .map(|code_actions| code_actions.menu(&core.editor))
+        })?;
+        if menu.view_id != self.view_id {
+            return None;
+        }
+
+        let mut origin = self.char_origin(menu.anchor, cx)?;
+        origin.y += self.style.line_height_in_pixels(cx.rem_size());
+        Some((origin, menu))
+    }
+
+    /// Signature help popup and the origin of the primary cursor it is placed above.
+    fn signature_help(
+        &self,
+        cx: &mut ViewContext<Self>,
+    ) -> Option<(Point<Pixels>, SignatureHelpPopup)> {
+        if !self.is_focused {
+            return None;
+        }
+        let (popup, cursor) = self.core.update(cx, |core, _cx| {
+            let popup = core
+                .compositor
+                .find::<SignatureHelp>()
+                .map(|help| help.popup(&core.editor))?;
+            let editor = &core.editor;
+            let view = editor.tree.get(popup.view_id);
+            let document = editor.document(view.doc)?;
+            let cursor = document
+                .selection(view.id)
+                .primary()
+                .cursor(document.text().slice(..));
+            Some((popup, cursor))
+        })?;
+        if popup.view_id != self.view_id {
+            return None;
+        }
+
+        let origin = self.char_origin(cursor, cx)?;
+        Some((origin, popup))
+    }
+
+    /// Hover popup and its origin relative to the view, placed right below the hovered char.
+    fn hover(&self, cx: &mut ViewContext<Self>) -> Option<(Point<Pixels>, HoverPopup)> {
+        let popup = self.core.update(cx, |core, _cx| {
+            core.compositor
+                .find::<Hover>()
+                .map(|hover| hover.popup(&core.editor))
+        })?;
+        if popup.view_id != self.view_id {
+            return None;
+        }
+
+        let mut origin = self.char_origin(popup.anchor, cx)?;
+        origin.y += self.style.line_height_in_pixels(cx.rem_size());
+        Some((origin, popup))
+    }
+
+    // requests hover documentation once the mouse rests on a char
+    fn hover_char(&mut self, char_idx: Option<usize>, cx: &mut ViewContext<Self>) {
+        if self.hovered_char == char_idx {
+            return;
+        }
+        self.hovered_char = char_idx;
+        let view_id = self.view_id;
+        self.hover_task = char_idx.map(|char_idx| {
+            cx.spawn(|this, mut cx| async move {
+                cx.background_executor().timer(HOVER_DELAY).await;
+                let _ = this.update(&mut cx, |this, cx| {
+                    this.input
+                        .update(cx, |_, cx| cx.emit(InputEvent::Hover { view_id, char_idx }))
+                });
+            })
+        });
+    }
+
+    // the cursor stays on while the document, the cursor or the mode change,
+    // blinking starts over once they stop changing
+    fn update_blink(&mut self, cx: &mut ViewContext<Self>) {
+        let config = cx.global::<crate::config::GuiConfig>().cursor.clone();
+        let state = {
+            let editor = &self.core.read(cx).editor;
+            editor.tree.try_get(self.view_id).and_then(|view| {
+                let doc = editor.document(view.doc)?;
+                let head = doc.selection(view.id).primary().head;
+                Some((doc.version(), head, editor.mode))
+            })
+        };
+        if !self.is_focused || !config.blink || state.is_none() {
+            self.cursor_visible = true;
+            self.blink_state = None;
+            self.blink_task = None;
+            return;
+        }
+        if self.blink_state == state && self.blink_task.is_some() {
+            return;
+        }
+
+        self.blink_state = state;
+        self.cursor_visible = true;
+        let interval = Duration::from_millis(config.blink_interval.max(1));
+        self.blink_task = Some(cx.spawn(|this, mut cx| async move {
+            loop {
+                cx.background_executor().timer(interval).await;
+                let blinked = this.update(&mut cx, |this, cx| {
+                    this.cursor_visible = !this.cursor_visible;
+                    cx.notify();
+                });
+                if blinked.is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    fn mouse_cell(&mut self, event: &CellMouseEvent, cx: &mut ViewContext<Self>) {
+        let view_id = self.view_id;
+        match *event {
+            CellMouseEvent::Down {
+                row,
+                col,
+                click_count,
+                add_cursor,
+            } => {
+                self.selection_drag = Some((row as i32, col));
+                self.input.update(cx, |_, cx| {
+                    cx.emit(InputEvent::Click {
+                        view_id,
+                        row,
+                        col,
+                        click_count,
+                        add_cursor,
+                    })
+                });
+            }
+            CellMouseEvent::Move { row, col } => {
+                if self.selection_drag.is_none() {
+                    return;
+                }
+                self.selection_drag = Some((row, col));
+                self.input
+                    .update(cx, |_, cx| cx.emit(InputEvent::Drag { view_id, row, col }));
+
+                let height = {
+                    let editor = &self.core.read(cx).editor;
+                    match editor.tree.try_get(view_id) {
+                        Some(view) => view.inner_height() as i32,
+                        None => return,
+                    }
+                };
+                if (0..height).contains(&row) {
+                    self.autoscroll_task = None;
+                } else if self.autoscroll_task.is_none() {
+                    self.autoscroll_task = Some(cx.spawn(|this, mut cx| async move {
+                        loop {
+                            cx.background_executor().timer(AUTOSCROLL_INTERVAL).await;
+                            let scrolled = this.update(&mut cx, |this, cx| {
+                                let Some((row, col)) = this.selection_drag else {
+                                    return false;
+                                };
+                                this.input.update(cx, |_, cx| {
+                                    cx.emit(InputEvent::Drag { view_id, row, col })
+                                });
+                                true
+                            });
+                            if !matches!(scrolled, Ok(true)) {
+                                break;
+                            }
+                        }
+                    }));
+                }
+            }
+            CellMouseEvent::Up => {
+                self.selection_drag = None;
+                self.autoscroll_task = None;
+            }
+        }
+    }
 }
 
 impl EventEmitter<DismissEvent> for DocumentView {}
@@ -130,6 +420,9 @@ impl Render for DocumentView {
             view.doc
         };
 
+        self.update_blink(cx);
+        let smooth_cursor = cx.global::<crate::config::GuiConfig>().cursor.smooth;
+
         let handle = ScrollHandle::default();
         let doc = DocumentElement::new(
             self.core.clone(),
@@ -139,6 +432,15 @@ impl Render for DocumentView {
             &self.focus,
             self.is_focused,
         )
+        .on_hover_char(
+            cx.listener(|view, char_idx: &Option<usize>, cx| view.hover_char(*char_idx, cx)),
+        )
+        .on_cell_mouse(cx.listener(|view, event: &CellMouseEvent, cx| view.mouse_cell(event, cx)))
+        .cursor(
+            self.cursor_visible,
+            smooth_cursor.then(|| self.cursor_motion.clone()),
+        )
+        .input_handler(cx.view().clone(), self.preedit.clone())
         .overflow_y_scroll()
         .track_scroll(&handle)
         .on_scroll_wheel(cx.listener(move |view, ev: &ScrollWheelEvent, cx| {
@@ -147,7 +449,28 @@ impl Render for DocumentView {
             let line_height = view.style.line_height_in_pixels(cx.rem_size());
 
             debug!("SCROLL WHEEL {:?}", ev);
-            let delta = ev.delta.pixel_delta(line_height);
+            let mut delta = ev.delta.pixel_delta(line_height);
+            // mouse wheels without a horizontal axis scroll sideways with shift
+            if ev.modifiers.shift && delta.x == px(0.) {
+                delta = point(delta.y, px(0.));
+            }
+            if delta.x != px(0.) {
+                let columns = delta.x / view.cell_width(cx);
+                let direction = if columns > 0. {
+                    Direction::Backward
+                } else {
+                    Direction::Forward
+                };
+                let column_count = 1 + columns.abs() as usize;
+
+                view.input.update(cx, |_, cx| {
+                    cx.emit(InputEvent::ScrollColumns {
+                        direction,
+                        column_count,
+                        view_id,
+                    })
+                });
+            }
             if delta.y != px(0.) {
                 let lines = delta.y / line_height;
                 let direction = if lines > 0. {
@@ -175,6 +498,11 @@ impl Render for DocumentView {
             self.style.clone(),
         );
 
+        let completion = self.completion_menu(cx);
+        let signature_help = self.signature_help(cx);
+        let hover = self.hover(cx);
+        let code_actions = self.code_action_menu(cx);
+
         let diags = {
             let theme = self.core.read(cx).editor.theme.clone();
 
@@ -193,6 +521,34 @@ impl Render for DocumentView {
             .flex_col()
             .child(doc)
             .child(status)
+            .when_some(completion, |this, (origin, menu)| {
+                this.child(div().absolute().left(origin.x).top(origin.y).child(menu))
+            })
+            .when_some(signature_help, |this, (origin, popup)| {
+                // zero height anchor at the cursor line, the popup grows upwards from it
+                this.child(
+                    div()
+                        .absolute()
+                        .left(origin.x)
+                        .top(origin.y)
+                        .w(px(600.))
+                        .h(px(0.))
+                        .child(
+                            div()
+                                .absolute()
+                                .bottom_0()
+                                .left_0()
+                                .max_w_full()
+                                .child(popup),
+                        ),
+                )
+            })
+            .when_some(hover, |this, (origin, popup)| {
+                this.child(div().absolute().left(origin.x).top(origin.y).child(popup))
+            })
+            .when_some(code_actions, |this, (origin, menu)| {
+                this.child(div().absolute().left(origin.x).top(origin.y).child(menu))
+            })
             .child(
                 div()
                     .flex()
@@ -214,6 +570,88 @@ impl FocusableView for DocumentView {
     }
 }
 
+// input methods see the document in UTF-16 code units, the composed
+// text is kept here and only reaches helix once it is committed
+impl ViewInputHandler for DocumentView {
+    fn text_for_range(
+        &mut self,
+        range: std::ops::Range<usize>,
+        cx: &mut ViewContext<Self>,
+    ) -> Option<String> {
+        let editor = &self.core.read(cx).editor;
+        let view = editor.tree.try_get(self.view_id)?;
+        let text = editor.document(view.doc)?.text();
+        let end = range.end.min(text.len_utf16_cu());
+        let start = range.start.min(end);
+        let slice = text.slice(text.utf16_cu_to_char(start)..text.utf16_cu_to_char(end));
+        Some(slice.to_string())
+    }
+
+    fn selected_text_range(
+        &mut self,
+        cx: &mut ViewContext<Self>,
+    ) -> Option<std::ops::Range<usize>> {
+        let (_, cursor) = self.primary_cursor(cx)?;
+        Some(cursor..cursor)
+    }
+
+    fn marked_text_range(&self, cx: &mut ViewContext<Self>) -> Option<std::ops::Range<usize>> {
+        let preedit = self.preedit.as_ref()?;
+        let (_, start) = self.primary_cursor(cx)?;
+        Some(start..start + preedit.encode_utf16().count())
+    }
+
+    fn unmark_text(&mut self, cx: &mut ViewContext<Self>) {
+        self.preedit = None;
+        cx.notify();
+    }
+
+    // the text always goes to every cursor the way typing does, whatever range is replaced
+    fn replace_text_in_range(
+        &mut self,
+        _range: Option<std::ops::Range<usize>>,
+        text: &str,
+        cx: &mut ViewContext<Self>,
+    ) {
+        self.preedit = None;
+        cx.notify();
+        if text.is_empty() {
+            return;
+        }
+        let view_id = self.view_id;
+        let text = text.to_string();
+        self.input
+            .update(cx, |_, cx| cx.emit(InputEvent::Text { view_id, text }));
+    }
+
+    fn replace_and_mark_text_in_range(
+        &mut self,
+        _range: Option<std::ops::Range<usize>>,
+        new_text: &str,
+        _new_selected_range: Option<std::ops::Range<usize>>,
+        cx: &mut ViewContext<Self>,
+    ) {
+        self.preedit = (!new_text.is_empty()).then(|| new_text.to_string().into());
+        cx.notify();
+    }
+
+    // the candidate window goes right below the primary cursor
+    fn bounds_for_range(
+        &mut self,
+        _range_utf16: std::ops::Range<usize>,
+        element_bounds: Bounds<Pixels>,
+        cx: &mut ViewContext<Self>,
+    ) -> Option<Bounds<Pixels>> {
+        let (cursor, _) = self.primary_cursor(cx)?;
+        let origin = self.char_origin(cursor, cx)?;
+        let size = size(
+            self.cell_width(cx),
+            self.style.line_height_in_pixels(cx.rem_size()),
+        );
+        Some(Bounds::new(element_bounds.origin + origin, size))
+    }
+}
+
 pub struct DocumentElement {
     core: Model<Core>,
     doc_id: DocumentId,
@@ -222,6 +660,35 @@ pub struct DocumentElement {
     interactivity: Interactivity,
     focus: FocusHandle,
     is_focused: bool,
+    // shows the picker preview instead of an editor view
+    is_preview: bool,
+    on_hover: Option<Rc<dyn Fn(&Option<usize>, &mut WindowContext)>>,
+    on_cell_mouse: Option<Rc<dyn Fn(&CellMouseEvent, &mut WindowContext)>>,
+    // off while blinking
+    cursor_visible: bool,
+    // animates the primary cursor when set
+    cursor_motion: Option<Rc<StdCell<Option<CursorMotion>>>>,
+    // the view taking text from input methods and the text being composed
+    input_handler: Option<View<DocumentView>>,
+    preedit: Option<SharedString>,
+}
+
+/// Left button mouse event on a document, in cells relative to the view.
+#[derive(Debug, Clone, Copy)]
+pub enum CellMouseEvent {
+    Down {
+        row: u16,
+        col: u16,
+        click_count: usize,
+        add_cursor: bool,
+    },
+    /// The mouse moved with the button held, anywhere in the window,
+    /// so the row can be above or below the view.
+    Move {
+        row: i32,
+        col: u16,
+    },
+    Up,
 }
 
 impl IntoElement for DocumentElement {
@@ -249,11 +716,135 @@ impl DocumentElement {
             interactivity: Interactivity::default(),
             focus: focus.clone(),
             is_focused,
+            is_preview: false,
+            on_hover: None,
+            on_cell_mouse: None,
+            cursor_visible: true,
+            cursor_motion: None,
+            input_handler: None,
+            preedit: None,
         }
         .track_focus(&focus)
         .element
     }
 
+    /// Element showing the file previewed by the picker, from `Core::picker_previews`.
+    pub fn preview(core: Model<Core>, style: TextStyle, focus: &FocusHandle) -> Self {
+        Self {
+            core,
+            doc_id: DocumentId::default(),
+            view_id: ViewId::default(),
+            style,
+            interactivity: Interactivity::default(),
+            focus: focus.clone(),
+            is_focused: false,
+            is_preview: true,
+            on_hover: None,
+            on_cell_mouse: None,
+            cursor_visible: true,
+            cursor_motion: None,
+            input_handler: None,
+            preedit: None,
+        }
+    }
+
+    /// Called with the char under the mouse whenever it moves.
+    pub fn on_hover_char(
+        mut self,
+        listener: impl Fn(&Option<usize>, &mut WindowContext) + 'static,
+    ) -> Self {
+        self.on_hover = Some(Rc::new(listener));
+        self
+    }
+
+    /// Called on left button presses, drags and releases.
+    pub fn on_cell_mouse(
+        mut self,
+        listener: impl Fn(&CellMouseEvent, &mut WindowContext) + 'static,
+    ) -> Self {
+        self.on_cell_mouse = Some(Rc::new(listener));
+        self
+    }
+
+    /// Blink phase of the cursors and the state of the smooth primary cursor.
+    pub fn cursor(
+        mut self,
+        visible: bool,
+        motion: Option<Rc<StdCell<Option<CursorMotion>>>>,
+    ) -> Self {
+        self.cursor_visible = visible;
+        self.cursor_motion = motion;
+        self
+    }
+
+    /// Takes text input while focused, `preedit` is drawn at the primary cursor.
+    pub fn input_handler(
+        mut self,
+        view: View<DocumentView>,
+        preedit: Option<SharedString>,
+    ) -> Self {
+        self.input_handler = Some(view);
+        self.preedit = preedit;
+        self
+    }
+
+    // char at a position in window coordinates, relative to the element `bounds`
+    fn char_at(
+        core: &Core,
+        view_id: ViewId,
+        position: Point<Pixels>,
+        bounds: Bounds<Pixels>,
+        cell_width: Pixels,
+        line_height: Pixels,
+    ) -> Option<usize> {
+        if !bounds.contains(&position) {
+            return None;
+        }
+        let editor = &core.editor;
+        let view = editor.tree.try_get(view_id)?;
+        let document = editor.document(view.doc)?;
+        let offset = position - bounds.origin - point(px(2.), px(1.));
+        let col = (offset.x / cell_width).max(0.) as u16;
+        let row = (offset.y / line_height).max(0.) as u16;
+        view.pos_at_screen_coords(document, view.area.y + row, view.area.x + col, true)
+    }
+
+    // the workspace sizes the whole tree, every view then takes the size
+    // it actually got on screen so that scrolling and paging match it
+    fn resize_view(editor: &mut Editor, view_id: ViewId, columns: usize, rows: usize) {
+        if !editor.tree.contains(view_id) {
+            return;
+        }
+        let scrolloff = editor.config().scrolloff;
+        let view = editor.tree.get_mut(view_id);
+        // the area includes a row for the status line, it is drawn below the document
+        let (width, height) = (columns as u16, rows as u16 + 1);
+        if view.area.width == width && view.area.height == height {
+            return;
+        }
+        view.area.width = width;
+        view.area.height = height;
+        let doc = &editor.documents[&view.doc];
+        view.ensure_cursor_in_view(doc, scrolloff);
+    }
+
+    // takes the fields instead of `&self`, the paint closure can't borrow
+    // the whole element while its interactivity is painting
+    fn document_and_view(
+        core: &Core,
+        is_preview: bool,
+        doc_id: DocumentId,
+        view_id: ViewId,
+    ) -> Option<(&Document, &View)> {
+        if is_preview {
+            let preview = core.picker_previews.shown()?;
+            Some((&preview.doc, &preview.view))
+        } else {
+            let editor = &core.editor;
+            Some((editor.document(doc_id)?, editor.tree.get(view_id)))
+        }
+    }
+
     // These 3 methods are just proxies for EditorView
     // TODO: make a PR to helix to extract them from helix_term into helix_view or smth.
     fn doc_diagnostics_highlights<'d>(
@@ -272,54 +863,23 @@ impl DocumentElement {
         EditorView::doc_syntax_highlights(doc, anchor, height, theme)
     }
 
-    fn doc_selection_highlights(
-        mode: helix_view::document::Mode,
-        doc: &Document,
-        view: &View,
-        theme: &Theme,
-        cursor_shape_config: &helix_view::editor::CursorShapeConfig,
-        is_window_focused: bool,
-    ) -> Vec<(usize, std::ops::Range<usize>)> {
-        EditorView::doc_selection_highlights(
-            mode,
-            doc,
-            view,
-            theme,
-            cursor_shape_config,
-            is_window_focused,
-        )
-    }
-
+    // selections and cursors are painted separately, not as text backgrounds
     fn overlay_highlights(
-        mode: helix_view::document::Mode,
         doc: &Document,
         view: &View,
         theme: &Theme,
-        cursor_shape_config: &helix_view::editor::CursorShapeConfig,
-        is_window_focused: bool,
         is_view_focused: bool,
     ) -> impl Iterator<Item = HighlightEvent> {
         let mut overlay_highlights =
             EditorView::empty_highlight_iter(doc, view.offset.anchor, view.inner_area(doc).height);
         if is_view_focused {
-            let highlights = helix_core::syntax::merge(
-                overlay_highlights,
-                Self::doc_selection_highlights(
-                    mode,
-                    doc,
-                    view,
-                    theme,
-                    cursor_shape_config,
-                    is_window_focused,
-                ),
-            );
             let focused_view_elements =
                 EditorView::highlight_focused_view_elements(view, doc, theme);
-            if focused_view_elements.is_empty() {
-                overlay_highlights = Box::new(highlights)
-            } else {
-                overlay_highlights =
-                    Box::new(helix_core::syntax::merge(highlights, focused_view_elements))
+            if !focused_view_elements.is_empty() {
+                overlay_highlights = Box::new(helix_core::syntax::merge(
+                    overlay_highlights,
+                    focused_view_elements,
+                ))
             }
         }
 
@@ -336,118 +896,188 @@ impl DocumentElement {
         overlay_highlights
     }
 
-    fn highlight(
+    /// Cursor and selection colours of the view, by mode like helix's
+    /// `ui.cursor.primary.insert` and friends.
+    fn selection_colors(
+        theme: &Theme,
+        mode: Mode,
+        primary: bool,
+        fg_color: Hsla,
+        bg_color: Hsla,
+    ) -> SelectionColors {
+        let mode = match mode {
+            Mode::Insert => "insert",
+            Mode::Select => "select",
+            Mode::Normal => "normal",
+        };
+        let (cursor, selection) = if primary {
+            (
+                theme.get(&format!("ui.cursor.primary.{mode}")),
+                theme.get("ui.selection.primary"),
+            )
+        } else {
+            (
+                theme.get(&format!("ui.cursor.{mode}")),
+                theme.get("ui.selection"),
+            )
+        };
+        SelectionColors {
+            // themes without cursor colours get an inverted cell
+            cursor: cursor.bg.and_then(color_to_hsla).unwrap_or(fg_color),
+            cursor_text: cursor.fg.and_then(color_to_hsla).unwrap_or(bg_color),
+            selection: selection.bg.and_then(color_to_hsla),
+        }
+    }
+
+    /// Lays out the visible part of the document the way helix formats it,
+    /// virtual text like inlay hints included, one row per visual line.
+    fn layout_rows(
         editor: &Editor,
         doc: &Document,
         view: &View,
         theme: &Theme,
         is_view_focused: bool,
-        anchor: usize,
-        lines: u16,
-        end_char: usize,
+        height: usize,
         fg_color: Hsla,
+        bg_color: Hsla,
         font: Font,
-    ) -> Vec<TextRun> {
-        let mut runs = vec![];
-        let overlay_highlights = Self::overlay_highlights(
-            editor.mode(),
-            doc,
-            view,
-            theme,
-            &editor.config().cursor_shape,
-            true,
-            is_view_focused,
-        );
-
-        let syntax_highlights = Self::doc_syntax_highlights(doc, anchor, lines, theme);
-
-        let mut syntax_styles = StyleIter {
+        join_ascii: bool,
+    ) -> Vec<Row> {
+        let text = doc.text().slice(..);
+        let offset = view.offset;
+        // soft wrap, the wrap indicator and its `ui.virtual.wrap` style come from the format
+        let viewport_width = view.inner_width(doc);
+        let text_format = doc.text_format(viewport_width, Some(theme));
+        let annotations = view.text_annotations(doc, Some(theme));
+
+        let overlay_highlights = Self::overlay_highlights(doc, view, theme, is_view_focused);
+        let syntax_highlights =
+            Self::doc_syntax_highlights(doc, offset.anchor, height as u16, theme);
+        let mut syntax_styles = StyleSpans::new(StyleIter {
             text_style: helix_view::graphics::Style::default(),
             active_highlights: Vec::with_capacity(64),
             highlight_iter: syntax_highlights,
             theme,
-        };
-
-        let mut overlay_styles = StyleIter {
+        });
+        let mut overlay_styles = StyleSpans::new(StyleIter {
             text_style: helix_view::graphics::Style::default(),
             active_highlights: Vec::with_capacity(64),
             highlight_iter: overlay_highlights,
             theme,
-        };
+        });
 
-        let mut syntax_span =
-            syntax_styles
-                .next()
-                .unwrap_or((helix_view::graphics::Style::default(), 0, usize::MAX));
-        let mut overlay_span = overlay_styles.next().unwrap_or((
-            helix_view::graphics::Style::default(),
-            0,
-            usize::MAX,
-        ));
-
-        let mut position = anchor;
-        loop {
-            let (syn_style, syn_start, syn_end) = syntax_span;
-            let (ovl_style, ovl_start, ovl_end) = overlay_span;
+        // the formatter starts at the beginning of the block containing the anchor
+        let (
+            Position {
+                row: row_offset, ..
+            },
+            mut char_pos,
+        ) = visual_offset_from_block(
+            text,
+            offset.anchor,
+            offset.anchor,
+            &text_format,
+            &annotations,
+        );
+        let row_offset = row_offset + offset.vertical_offset;
+        let (mut formatter, _) = DocumentFormatter::new_at_prev_checkpoint(
+            text,
+            &text_format,
+            &annotations,
+            offset.anchor,
+        );
 
-            /* if we are between highlights, insert default style */
-            let (style, is_default) = if position < syn_start && position < ovl_start {
-                (helix_view::graphics::Style::default(), true)
-            } else {
-                let mut style = helix_view::graphics::Style::default();
-                if position >= syn_start && position < syn_end {
-                    style = style.patch(syn_style);
-                }
-                if position >= ovl_start && position < ovl_end {
-                    style = style.patch(ovl_style);
-                }
-                (style, false)
+        let mut rows: Vec<Row> = Vec::with_capacity(height);
+        // visual line and document line of the last grapheme
+        let mut last_pos: Option<(usize, usize)> = None;
+        loop {
+            // the line index of the next grapheme, must be called before `next`
+            let doc_line = formatter.line_pos();
+            let Some((grapheme, pos)) = formatter.next() else {
+                break;
             };
+            let new_visual_line = last_pos.map_or(true, |(row, _)| row != pos.row);
+            let first_visual_line = last_pos.map_or(true, |(_, line)| line != doc_line);
+            last_pos = Some((pos.row, doc_line));
 
-            let fg = style
-                .fg
-                .and_then(|fg| color_to_hsla(fg))
-                .unwrap_or(fg_color);
-            let bg = style.bg.and_then(|bg| color_to_hsla(bg));
-            let len = if is_default {
-                std::cmp::min(syn_start, ovl_start) - position
-            } else {
-                std::cmp::min(
-                    syn_end.checked_sub(position).unwrap_or(usize::MAX),
-                    ovl_end.checked_sub(position).unwrap_or(usize::MAX),
-                )
-            };
-            let underline = style.underline_color.and_then(color_to_hsla);
-            let underline = underline.map(|color| UnderlineStyle {
-                thickness: px(1.),
-                color: Some(color),
-                wavy: true,
-            });
+            // skip any graphemes on visual lines before the view offset
+            if pos.row < row_offset {
+                char_pos += grapheme.doc_chars();
+                continue;
+            }
+            let visual_line = pos.row - row_offset;
+            if visual_line >= height {
+                break;
+            }
 
-            let len = std::cmp::min(len, end_char);
+            if new_visual_line || rows.is_empty() {
+                rows.push(Row {
+                    join_ascii,
+                    segments: Vec::new(),
+                    cells: Vec::new(),
+                    ends_line: false,
+                    pos: LinePos {
+                        first_visual_line,
+                        doc_line,
+                        visual_line: visual_line as u16,
+                        start_char_idx: char_pos,
+                    },
+                });
+            }
 
-            let run = TextRun {
-                len,
-                font: font.clone(),
-                color: fg,
-                background_color: bg,
-                underline,
-                strikethrough: None,
+            let style = match grapheme.source {
+                GraphemeSource::VirtualText { highlight } => highlight
+                    .map(|highlight| theme.highlight(highlight.0))
+                    .unwrap_or_default(),
+                GraphemeSource::Document { .. } => syntax_styles
+                    .style_at(char_pos)
+                    .patch(overlay_styles.style_at(char_pos)),
             };
-            runs.push(run);
-            position += len;
+            let char_idx = char_pos;
+            char_pos += grapheme.doc_chars();
 
-            if position >= end_char {
-                break;
+            // skip graphemes scrolled out of view horizontally, a grapheme
+            // cut off by the left edge is replaced by its visible cells
+            let mut width = grapheme.grapheme.width();
+            if pos.col + width <= offset.horizontal_offset {
+                continue;
             }
-            if position >= syn_end {
-                syntax_span = syntax_styles.next().unwrap_or((style, 0, usize::MAX));
+            let cut_off = offset.horizontal_offset.saturating_sub(pos.col);
+            let col = pos.col + cut_off - offset.horizontal_offset;
+            if col >= viewport_width as usize {
+                continue;
             }
-            if position >= ovl_end {
-                overlay_span = overlay_styles.next().unwrap_or((style, 0, usize::MAX));
+
+            let spaces;
+            let symbol: &str = match &grapheme.grapheme {
+                _ if cut_off > 0 => {
+                    width -= cut_off;
+                    spaces = " ".repeat(width);
+                    &spaces
+                }
+                // the newline gets a cell so that selections and cursors on it are visible
+                Grapheme::Newline => " ",
+                // tabs are expanded to the next tab stop
+                Grapheme::Tab { width } => {
+                    spaces = " ".repeat(*width);
+                    &spaces
+                }
+                Grapheme::Other { g } => g,
+            };
+            let run = text_run(style, symbol.len(), &font, fg_color, bg_color);
+            let row = rows.last_mut().unwrap();
+            if let GraphemeSource::Document { .. } = grapheme.source {
+                row.cells.push(Cell {
+                    char_idx,
+                    chars: grapheme.doc_chars(),
+                    col,
+                    width,
+                });
+                row.ends_line = matches!(grapheme.grapheme, Grapheme::Newline);
             }
+            row.push(symbol, col, width, run);
         }
-        runs
+        rows
     }
 }
 
@@ -470,15 +1100,6 @@ pub struct DocumentLayout {
     hitbox: Option<Hitbox>,
 }
 
-struct RopeWrapper<'a>(RopeSlice<'a>);
-
-impl<'a> Into<SharedString> for RopeWrapper<'a> {
-    fn into(self) -> SharedString {
-        let cow: Cow<'_, str> = self.0.into();
-        cow.to_string().into() // this is crazy
-    }
-}
-
 impl Element for DocumentElement {
     type RequestLayoutState = ();
 
@@ -512,32 +1133,29 @@ impl Element for DocumentElement {
         self.interactivity
             .prepaint(id, bounds, bounds.size, cx, |_, _, hitbox, cx| {
                 cx.with_content_mask(Some(ContentMask { bounds }), |cx| {
-                    let font_id = cx.text_system().resolve_font(&self.style.font());
                     let font_size = self.style.font_size.to_pixels(cx.rem_size());
                     let line_height = self.style.line_height_in_pixels(cx.rem_size());
-                    let em_width = cx
-                        .text_system()
-                        .typographic_bounds(font_id, font_size, 'm')
-                        .unwrap()
-                        .size
-                        .width;
-                    let cell_width = cx
-                        .text_system()
-                        .advance(font_id, font_size, 'm')
-                        .unwrap()
-                        .width;
-                    let columns = (bounds.size.width / em_width).floor() as usize;
+                    let cell_width = crate::utils::cell_width(&self.style, cx);
+                    // every grapheme is painted on a grid of `cell_width` wide columns
+                    let columns = ((bounds.size.width - px(2.)) / cell_width).floor() as usize;
                     let rows = (bounds.size.height / line_height).floor() as usize;
 
+                    let is_preview = self.is_preview;
+                    let view_id = self.view_id;
                     core.update(cx, |core, _cx| {
-                        let rect = helix_view::graphics::Rect {
-                            x: 0,
-                            y: 0,
-                            width: columns as u16,
-                            height: rows as u16,
-                        };
-                        let editor = &mut core.editor;
-                        editor.resize(rect)
+                        if is_preview {
+                            let rect = helix_view::graphics::Rect {
+                                x: 0,
+                                y: 0,
+                                width: columns as u16,
+                                height: rows as u16,
+                            };
+                            if let Some(preview) = core.picker_previews.shown_mut() {
+                                preview.resize(rect);
+                            }
+                        } else {
+                            Self::resize_view(&mut core.editor, view_id, columns, rows);
+                        }
                     });
                     DocumentLayout {
                         hitbox,
@@ -566,20 +1184,81 @@ impl Element for DocumentElement {
                 cx.focus(&focus);
             });
 
+        if let Some(on_cell_mouse) = self.on_cell_mouse.clone() {
+            let cell_width = after_layout.cell_width;
+            let line_height = after_layout.line_height;
+            let cell = move |position: Point<Pixels>| {
+                let offset = position - bounds.origin - point(px(2.), px(1.));
+                let row = (offset.y / line_height).floor() as i32;
+                let col = (offset.x / cell_width).max(0.) as u16;
+                (row, col)
+            };
+
+            let on_down = on_cell_mouse.clone();
+            self.interactivity
+                .on_mouse_down(MouseButton::Left, move |ev, cx| {
+                    let (row, col) = cell(ev.position);
+                    let event = CellMouseEvent::Down {
+                        row: row.max(0) as u16,
+                        col,
+                        click_count: ev.click_count,
+                        add_cursor: ev.modifiers.alt,
+                    };
+                    on_down(&event, cx);
+                });
+            // selecting goes on when the mouse leaves the view
+            let on_move = on_cell_mouse.clone();
+            cx.on_mouse_event(move |ev: &MouseMoveEvent, phase, cx| {
+                if phase == DispatchPhase::Bubble && ev.pressed_button == Some(MouseButton::Left) {
+                    let (row, col) = cell(ev.position);
+                    on_move(&CellMouseEvent::Move { row, col }, cx);
+                }
+            });
+            cx.on_mouse_event(move |ev: &MouseUpEvent, phase, cx| {
+                if phase == DispatchPhase::Bubble && ev.button == MouseButton::Left {
+                    on_cell_mouse(&CellMouseEvent::Up, cx);
+                }
+            });
+        }
+
+        if let Some(on_hover) = self.on_hover.clone() {
+            let core = self.core.clone();
+            let view_id = self.view_id;
+            let cell_width = after_layout.cell_width;
+            let line_height = after_layout.line_height;
+            self.interactivity.on_mouse_move(move |ev, cx| {
+                let char_idx = Self::char_at(
+                    core.read(cx),
+                    view_id,
+                    ev.position,
+                    bounds,
+                    cell_width,
+                    line_height,
+                );
+                on_hover(&char_idx, cx);
+            });
+        }
+
         let is_focused = self.is_focused;
+        if let Some(view) = self.input_handler.take().filter(|_| is_focused) {
+            cx.handle_input(&self.focus, ElementInputHandler::new(bounds, view));
+        }
 
         self.interactivity
             .paint(id, bounds, after_layout.hitbox.as_ref(), cx, |_, cx| {
                 let core = self.core.read(cx);
                 let editor = &core.editor;
 
-                let view = editor.tree.get(self.view_id);
+                let Some((document, view)) =
+                    Self::document_and_view(core, self.is_preview, self.doc_id, self.view_id)
+                else {
+                    return;
+                };
                 let _viewport = view.area;
 
                 let theme = &editor.theme;
                 let default_style = theme.get("ui.background");
                 let bg_color = color_to_hsla(default_style.bg.unwrap()).unwrap_or(black());
-                let cursor_style = theme.get("ui.cursor.primary");
                 let bg = fill(bounds, bg_color);
                 let fg_color = color_to_hsla(
                     default_style
@@ -588,15 +1267,16 @@ impl Element for DocumentElement {
                 )
                 .unwrap_or(white());
 
-                let document = editor.document(self.doc_id).unwrap();
-                let text = document.text();
-
-                let (_, cursor_kind) = editor.cursor();
-                let primary_idx = document
-                    .selection(self.view_id)
-                    .primary()
-                    .cursor(text.slice(..));
-                let cursor_pos = view.screen_coords_at_pos(document, text.slice(..), primary_idx);
+                // owned, the editor can't stay borrowed while painting
+                let text = document.text().clone();
+                // the focused view takes the kind helix reports, which may hide the
+                // cursor, the others get the `cursor-shape` of the mode
+                let cursor_kind = if editor.tree.focus == view.id {
+                    editor.cursor().1
+                } else {
+                    editor.config().cursor_shape.from_mode(editor.mode())
+                };
+                let selection = document.selection(view.id).clone();
 
                 let gutter_width = view.gutter_offset(document);
                 let gutter_overflow = gutter_width == 0;
@@ -604,36 +1284,33 @@ impl Element for DocumentElement {
                     debug!("need to render gutter {}", gutter_width);
                 }
 
-                let cursor_text = None; // TODO
-
-                let _cursor_row = cursor_pos.map(|p| p.row);
-                let anchor = view.offset.anchor;
-                let total_lines = text.len_lines();
-                let first_row = text.char_to_line(anchor.min(text.len_chars()));
-                // println!("first row is {}", row);
-                let last_row = (first_row + after_layout.rows + 1).min(total_lines);
-                // println!("first row is {first_row} last row is {last_row}");
-                let end_char = text.line_to_char(std::cmp::min(last_row, total_lines));
-
-                let text_view = text.slice(anchor..end_char);
-                let str: SharedString = RopeWrapper(text_view).into();
-
-                let runs = Self::highlight(
+                let mut rows = Self::layout_rows(
                     &editor,
                     document,
                     view,
                     theme,
                     self.is_focused,
-                    anchor,
-                    total_lines.min(after_layout.rows + 1) as u16,
-                    end_char,
+                    after_layout.rows,
                     fg_color,
+                    bg_color,
                     self.style.font(),
+                    cx.global::<crate::FontSettings>().letter_spacing == px(0.),
                 );
-                let shaped_lines = cx
-                    .text_system()
-                    .shape_text(str, after_layout.font_size, &runs, None)
-                    .unwrap();
+                for segment in rows.iter_mut().flat_map(|row| &mut row.segments) {
+                    segment.shape(after_layout, cx.text_system());
+                }
+                let line_positions: Vec<LinePos> = rows.iter().map(|row| row.pos).collect();
+                let primary_colors =
+                    Self::selection_colors(theme, editor.mode(), true, fg_color, bg_color);
+                let secondary_colors =
+                    Self::selection_colors(theme, editor.mode(), false, fg_color, bg_color);
+                let colors = |idx| {
+                    if idx == selection.primary_index() {
+                        primary_colors
+                    } else {
+                        secondary_colors
+                    }
+                };
 
                 cx.paint_quad(bg);
 
@@ -641,40 +1318,153 @@ impl Element for DocumentElement {
                 origin.x += px(2.) + (after_layout.cell_width * gutter_width as f32);
                 origin.y += px(1.);
 
-                // draw document
-                for line in shaped_lines {
-                    line.paint(origin, after_layout.line_height, cx).unwrap();
-                    origin.y += after_layout.line_height;
-                }
-                // draw cursor
-                if self.is_focused {
-                    match (cursor_pos, cursor_kind) {
-                        (Some(position), kind) => {
-                            let helix_core::Position { row, col } = position;
-                            let origin_y = after_layout.line_height * row as f32;
-                            let origin_x =
-                                after_layout.cell_width * ((col + gutter_width as usize) as f32);
-                            let mut cursor_fg = cursor_style
-                                .bg
-                                .and_then(|fg| color_to_hsla(fg))
-                                .unwrap_or(fg_color);
-                            cursor_fg.a = 0.5;
-
-                            let mut cursor = Cursor {
-                                origin: gpui::Point::new(origin_x, origin_y),
-                                kind,
-                                color: cursor_fg,
-                                block_width: after_layout.cell_width,
-                                line_height: after_layout.line_height,
-                                text: cursor_text,
-                            };
-                            let mut origin = bounds.origin;
-                            origin.x += px(2.);
-                            origin.y += px(1.);
+                // draw selections and document, clipped so that scrolled text doesn't run into the gutter
+                let text_bounds =
+                    Bounds::from_corners(point(origin.x, bounds.origin.y), bounds.lower_right());
+                cx.with_content_mask(
+                    Some(ContentMask {
+                        bounds: text_bounds,
+                    }),
+                    |cx| {
+                        if self.is_focused {
+                            for (idx, range) in selection.iter().enumerate() {
+                                let Some(color) = colors(idx).selection else {
+                                    continue;
+                                };
+                                for row in &rows {
+                                    let Some((start, end)) =
+                                        row.selected_cols(range.from(), range.to())
+                                    else {
+                                        continue;
+                                    };
+                                    let y = origin.y
+                                        + after_layout.line_height * row.pos.visual_line as f32;
+                                    let left = origin.x + after_layout.cell_width * start as f32;
+                                    let right = end.map_or(text_bounds.right(), |end| {
+                                        origin.x + after_layout.cell_width * end as f32
+                                    });
+                                    let bounds = Bounds::from_corners(
+                                        point(left, y),
+                                        point(right, y + after_layout.line_height),
+                                    );
+                                    cx.paint_quad(fill(bounds, color));
+                                }
+                            }
+                        }
 
-                            cursor.paint(origin, cx);
+                        for row in &rows {
+                            let y =
+                                origin.y + after_layout.line_height * row.pos.visual_line as f32;
+                            for segment in &row.segments {
+                                let x = origin.x + after_layout.cell_width * segment.col as f32;
+                                segment.paint(point(x, y), after_layout, cx);
+                            }
+                        }
+                    },
+                );
+                // draw cursors, the char under a block cursor in the inverted colour
+                if self.is_focused && self.cursor_visible && cursor_kind != CursorKind::Hidden {
+                    let text = text.slice(..);
+                    for (idx, range) in selection.iter().enumerate() {
+                        let char_idx = range.cursor(text);
+                        let Some((visual_line, cell)) = rows.iter().find_map(|row| {
+                            let cell = row.cells.iter().find(|cell| cell.char_idx == char_idx)?;
+                            Some((row.pos.visual_line, *cell))
+                        }) else {
+                            continue;
+                        };
+                        if self.preedit.is_some() && idx == selection.primary_index() {
+                            continue;
                         }
-                        (None, _) => {}
+                        let colors = colors(idx);
+
+                        let cursor_text = match cursor_kind {
+                            CursorKind::Block => {
+                                let end = next_grapheme_boundary(text, char_idx);
+                                let grapheme: Cow<str> = text.slice(char_idx..end).into();
+                                let grapheme = grapheme.trim_end_matches(['\r', '\n']);
+                                (!grapheme.is_empty() && grapheme != "\t").then(|| {
+                                    let run = TextRun {
+                                        len: grapheme.len(),
+                                        font: self.style.font(),
+                                        color: colors.cursor_text,
+                                        background_color: None,
+                                        underline: None,
+                                        strikethrough: None,
+                                    };
+                                    cx.text_system()
+                                        .shape_line(
+                                            grapheme.to_string().into(),
+                                            after_layout.font_size,
+                                            &[run],
+                                        )
+                                        .unwrap()
+                                })
+                            }
+                            _ => None,
+                        };
+
+                        let target = point(
+                            after_layout.cell_width * (gutter_width as usize + cell.col) as f32,
+                            after_layout.line_height * visual_line as f32,
+                        );
+                        let origin = match &self.cursor_motion {
+                            Some(motion) if idx == selection.primary_index() => {
+                                CursorMotion::origin(motion, target, cx)
+                            }
+                            _ => target,
+                        };
+                        // the char is only drawn once the cursor got to it
+                        let cursor_text = cursor_text.filter(|_| origin == target);
+
+                        let mut cursor = Cursor {
+                            origin,
+                            kind: cursor_kind,
+                            color: colors.cursor,
+                            block_width: after_layout.cell_width * cell.width.max(1) as f32,
+                            line_height: after_layout.line_height,
+                            text: cursor_text,
+                        };
+                        let mut origin = bounds.origin;
+                        origin.x += px(2.);
+                        origin.y += px(1.);
+
+                        cursor.paint(origin, cx);
+                    }
+                }
+                // draw the text being composed over the primary cursor, underlined
+                if let Some(preedit) = self.preedit.clone().filter(|_| self.is_focused) {
+                    let char_idx = selection.primary().cursor(text.slice(..));
+                    let cell = rows.iter().find_map(|row| {
+                        let cell = row.cells.iter().find(|cell| cell.char_idx == char_idx)?;
+                        Some((row.pos.visual_line, cell.col))
+                    });
+                    if let Some((visual_line, col)) = cell {
+                        let run = TextRun {
+                            len: preedit.len(),
+                            font: self.style.font(),
+                            color: fg_color,
+                            background_color: None,
+                            underline: Some(UnderlineStyle {
+                                thickness: px(1.),
+                                color: Some(fg_color),
+                                wavy: false,
+                            }),
+                            strikethrough: None,
+                        };
+                        let shaped = cx
+                            .text_system()
+                            .shape_line(preedit, after_layout.font_size, &[run])
+                            .unwrap();
+                        let origin = point(
+                            origin.x + after_layout.cell_width * col as f32,
+                            origin.y + after_layout.line_height * visual_line as f32,
+                        );
+                        cx.paint_quad(fill(
+                            Bounds::new(origin, size(shaped.width, after_layout.line_height)),
+                            bg_color,
+                        ));
+                        shaped.paint(origin, after_layout.line_height, cx).unwrap();
                     }
                 }
                 // draw gutter
@@ -686,22 +1476,17 @@ impl Element for DocumentElement {
                     let core = self.core.read(cx);
                     let editor = &core.editor;
                     let theme = &editor.theme;
-                    let view = editor.tree.get(self.view_id);
-                    let document = editor.document(self.doc_id).unwrap();
-                    let lines = (first_row..last_row)
-                        .enumerate()
-                        .map(|(visual_line, doc_line)| LinePos {
-                            first_visual_line: true,
-                            doc_line,
-                            visual_line: visual_line as u16,
-                            start_char_idx: 0,
-                        });
-
+                    let Some((document, view)) =
+                        Self::document_and_view(core, self.is_preview, self.doc_id, self.view_id)
+                    else {
+                        return;
+                    };
                     let mut gutter = Gutter {
                         after_layout,
                         text_system: cx.text_system().clone(),
                         lines: Vec::new(),
                         style: self.style.clone(),
+                        bg_color,
                         origin: gutter_origin,
                     };
                     {
@@ -714,7 +1499,7 @@ impl Element for DocumentElement {
                             is_focused,
                             &mut gutters,
                         );
-                        for line in lines {
+                        for line in line_positions {
                             for gut in &mut gutters {
                                 gut(line, &mut gutter)
                             }
@@ -733,6 +1518,8 @@ struct Gutter<'a> {
     text_system: std::sync::Arc<WindowTextSystem>,
     lines: Vec<(Point<Pixels>, ShapedLine)>,
     style: TextStyle,
+    // background of the document, reversed styles draw their text in it
+    bg_color: Hsla,
     origin: Point<Pixels>,
 }
 
@@ -766,7 +1553,6 @@ impl<'a> Gutter<'a> {
             let mut text = String::with_capacity(width);
             let cursors = cursors.clone();
             let gutter_decoration = move |pos: LinePos, renderer: &mut Self| {
-                // TODO handle softwrap in gutters
                 let selected = cursors.contains(&pos.doc_line);
                 let x = offset;
                 let y = pos.visual_line;
@@ -806,19 +1592,14 @@ impl<'a> GutterRenderer for Gutter<'a> {
         let origin_y = self.origin.y + self.after_layout.line_height * y as f32;
         let origin_x = self.origin.x + self.after_layout.cell_width * x as f32;
 
-        let fg_color = style
-            .fg
-            .and_then(color_to_hsla)
-            .unwrap_or(hsla(0., 0., 1., 1.));
         if let Some(text) = text {
-            let run = TextRun {
-                len: text.len(),
-                font: self.style.font(),
-                color: fg_color,
-                background_color: None,
-                underline: None,
-                strikethrough: None,
-            };
+            let run = text_run(
+                style,
+                text.len(),
+                &self.style.font(),
+                hsla(0., 0., 1., 1.),
+                self.bg_color,
+            );
             let shaped = self
                 .text_system
                 .shape_line(text.to_string().into(), self.after_layout.font_size, &[run])
@@ -860,11 +1641,17 @@ impl Cursor {
                     + gpui::Point::new(Pixels::ZERO, self.line_height - px(2.0)),
                 size: size(self.block_width, px(2.0)),
             },
-            CursorKind::Hidden => todo!(),
+            CursorKind::Hidden => Bounds {
+                origin: self.origin + origin,
+                size: Size::default(),
+            },
         }
     }
 
     pub fn paint(&mut self, origin: gpui::Point<Pixels>, cx: &mut WindowContext) {
+        if self.kind == CursorKind::Hidden {
+            return;
+        }
         let bounds = self.bounds(origin);
 
         let cursor = fill(bounds, self.color);
@@ -878,6 +1665,62 @@ impl Cursor {
     }
 }
 
+/// The smooth cursor moving from `from` to `to`, positions relative to the view.
+#[derive(Debug, Clone, Copy)]
+pub struct CursorMotion {
+    from: Point<Pixels>,
+    to: Point<Pixels>,
+    start: Instant,
+}
+
+impl CursorMotion {
+    /// Where the cursor is drawn on its way to `target`, keeps
+    /// the view painting until it gets there.
+    fn origin(
+        motion: &StdCell<Option<Self>>,
+        target: Point<Pixels>,
+        cx: &mut WindowContext,
+    ) -> Point<Pixels> {
+        let now = Instant::now();
+        let current = match motion.get() {
+            Some(current) if current.to == target => current,
+            // retarget from wherever the cursor is at the moment
+            Some(current) => Self {
+                from: current.position(now),
+                to: target,
+                start: now,
+            },
+            None => Self {
+                from: target,
+                to: target,
+                start: now,
+            },
+        };
+        motion.set(Some(current));
+
+        let position = current.position(now);
+        if position != target {
+            cx.request_animation_frame();
+        }
+        position
+    }
+
+    fn position(&self, now: Instant) -> Point<Pixels> {
+        let progress = (now.duration_since(self.start).as_secs_f32()
+            / CURSOR_MOTION_DURATION.as_secs_f32())
+        .min(1.);
+        if progress >= 1. {
+            return self.to;
+        }
+        // ease out, fast at first and slowing down near the target
+        let t = 1. - (1. - progress).powi(3);
+        point(
+            self.from.x + (self.to.x - self.from.x) * t,
+            self.from.y + (self.to.y - self.from.y) * t,
+        )
+    }
+}
+
 type GutterDecoration<'a, T> = Box<dyn FnMut(LinePos, &mut T) + 'a>;
 
 trait GutterRenderer {
@@ -907,6 +1750,187 @@ struct LinePos {
     pub start_char_idx: usize,
 }
 
+/// A visual line of the view, with its virtual text inlined.
+struct Row {
+    // ASCII graphemes are shaped together, unless letter spacing keeps
+    // them from lining up with the cells
+    join_ascii: bool,
+    segments: Vec<Segment>,
+    // document graphemes of the line, in order
+    cells: Vec<Cell>,
+    // the last grapheme is a line break
+    ends_line: bool,
+    pos: LinePos,
+}
+
+/// Document grapheme painted from the cell at `col`.
+#[derive(Debug, Clone, Copy)]
+struct Cell {
+    char_idx: usize,
+    chars: usize,
+    col: usize,
+    width: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SelectionColors {
+    cursor: Hsla,
+    cursor_text: Hsla,
+    selection: Option<Hsla>,
+}
+
+/// Graphemes shaped together and painted from the cell at `col`.
+/// Only ASCII graphemes share a segment, any other grapheme gets its own so
+/// that fallback fonts and double width chars can't shift the following cells.
+struct Segment {
+    col: usize,
+    // cells covered by the segment
+    width: usize,
+    text: String,
+    runs: Vec<TextRun>,
+    // shaped lines of the segment by their column in it, set by `shape`
+    shaped: Vec<(usize, ShapedLine)>,
+}
+
+impl Segment {
+    /// Shapes the segment once its text is complete. Font features like
+    /// ligatures or alternates can shape joined graphemes wider or narrower
+    /// than their cells, they are then shaped one by one to stay on the grid.
+    fn shape(&mut self, layout: &DocumentLayout, text_system: &WindowTextSystem) {
+        let shaped = text_system
+            .shape_line(self.text.clone().into(), layout.font_size, &self.runs)
+            .unwrap();
+        let fits = (shaped.width - layout.cell_width * self.width as f32).abs() < px(0.5);
+        if !self.is_joined() || fits {
+            self.shaped = vec![(0, shaped)];
+            return;
+        }
+        self.shaped = self
+            .cells()
+            .into_iter()
+            .map(|(col, text, run)| {
+                let shaped = text_system
+                    .shape_line(text.to_string().into(), layout.font_size, &[run])
+                    .unwrap();
+                (col, shaped)
+            })
+            .collect();
+    }
+
+    fn is_joined(&self) -> bool {
+        self.width > 1 && self.text.is_ascii() && self.text.len() == self.width
+    }
+
+    /// Text and style of each cell of a joined segment, by their column in it.
+    /// Joined graphemes are ASCII, a byte per cell.
+    fn cells(&self) -> Vec<(usize, &str, TextRun)> {
+        let mut cells = Vec::with_capacity(self.width);
+        let mut col = 0;
+        for run in &self.runs {
+            for _ in 0..run.len {
+                let run = TextRun {
+                    len: 1,
+                    ..run.clone()
+                };
+                cells.push((col, &self.text[col..col + 1], run));
+                col += 1;
+            }
+        }
+        cells
+    }
+
+    /// Paints the shaped segment from `origin`.
+    fn paint(&self, origin: Point<Pixels>, layout: &DocumentLayout, cx: &mut WindowContext) {
+        for (col, shaped) in &self.shaped {
+            let origin = origin + point(layout.cell_width * *col as f32, px(0.));
+            shaped.paint(origin, layout.line_height, cx).unwrap();
+        }
+    }
+}
+
+impl Row {
+    /// Columns covered by the chars `from..to` on this line, the end is `None`
+    /// when the selection goes on past the end of the line.
+    fn selected_cols(&self, from: usize, to: usize) -> Option<(usize, Option<usize>)> {
+        let (first_cell, last_cell) = (self.cells.first()?, self.cells.last()?);
+        if to <= first_cell.char_idx || from > last_cell.char_idx {
+            return None;
+        }
+        let mut selected = self
+            .cells
+            .iter()
+            .filter(|cell| from <= cell.char_idx && cell.char_idx < to);
+        let first = selected.next()?;
+        let last = selected.last().unwrap_or(first);
+        let goes_on = last.char_idx == last_cell.char_idx
+            && (self.ends_line || to > last.char_idx + last.chars);
+        Some((first.col, (!goes_on).then_some(last.col + last.width)))
+    }
+
+    fn push(&mut self, symbol: &str, col: usize, width: usize, run: TextRun) {
+        let is_ascii = self.join_ascii && symbol.is_ascii() && symbol.len() == width;
+        let extends_last = matches!(
+            self.segments.last(),
+            Some(last) if is_ascii && last.text.is_ascii() && last.col + last.width == col
+        );
+        if !extends_last {
+            self.segments.push(Segment {
+                col,
+                width: 0,
+                text: String::new(),
+                runs: Vec::new(),
+                shaped: Vec::new(),
+            });
+        }
+        let segment = self.segments.last_mut().unwrap();
+        segment.width += width;
+        segment.text.push_str(symbol);
+        match segment.runs.last_mut() {
+            Some(last)
+                if last.font == run.font
+                    && last.color == run.color
+                    && last.background_color == run.background_color
+                    && last.underline == run.underline
+                    && last.strikethrough == run.strikethrough =>
+            {
+                last.len += run.len
+            }
+            _ => segment.runs.push(run),
+        }
+    }
+}
+
+/// The span of a `StyleIter` covering the char being laid out.
+struct StyleSpans<'a, H: Iterator<Item = HighlightEvent>> {
+    iter: StyleIter<'a, H>,
+    span: (helix_view::graphics::Style, usize, usize),
+}
+
+impl<'a, H: Iterator<Item = HighlightEvent>> StyleSpans<'a, H> {
+    fn new(iter: StyleIter<'a, H>) -> Self {
+        Self {
+            iter,
+            span: (helix_view::graphics::Style::default(), 0, 0),
+        }
+    }
+
+    // chars have to be looked up in increasing order
+    fn style_at(&mut self, char_idx: usize) -> helix_view::graphics::Style {
+        while char_idx >= self.span.2 {
+            self.span = self.iter.next().unwrap_or((
+                helix_view::graphics::Style::default(),
+                usize::MAX,
+                usize::MAX,
+            ));
+        }
+        if char_idx >= self.span.1 {
+            self.span.0
+        } else {
+            helix_view::graphics::Style::default()
+        }
+    }
+}
+
 // TODO: copy-pasted from helix_term ui/document.rs
 
 /// A wrapper around a HighlightIterator
@@ -983,6 +2007,7 @@ impl Render for DiagnosticView {
         };
 
         let font = cx.global::<crate::FontSettings>().fixed_font.clone();
+        let style = cx.global::<crate::FontSettings>().ui_text_style();
         let source_and_code = self.diagnostic.source.as_ref().and_then(|src| {
             let code = self.diagnostic.code.as_ref();
             let code_str = code.map(|code| match code {
@@ -1001,7 +2026,8 @@ impl Render for DiagnosticView {
             .flex()
             .flex_col()
             .font(font)
-            .text_size(px(12.))
+            .text_size(style.font_size)
+            .line_height(style.line_height)
             .text_color(fg)
             .bg(bg)
             .child(
@@ -1016,3 +2042,99 @@ impl Render for DiagnosticView {
             .child(div().flex_col().child(self.diagnostic.message.clone()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(len: usize, color: Hsla) -> TextRun {
+        TextRun {
+            len,
+            font: font("Zed Mono"),
+            color,
+            background_color: None,
+            underline: None,
+            strikethrough: None,
+        }
+    }
+
+    fn row(join_ascii: bool) -> Row {
+        Row {
+            join_ascii,
+            segments: Vec::new(),
+            cells: Vec::new(),
+            ends_line: false,
+            pos: LinePos {
+                first_visual_line: true,
+                doc_line: 0,
+                visual_line: 0,
+                start_char_idx: 0,
+            },
+        }
+    }
+
+    // column of every char painted by the row, as the segments place them
+    fn painted_cols(row: &Row) -> Vec<(usize, String)> {
+        let mut cols = Vec::new();
+        for segment in &row.segments {
+            if segment.is_joined() {
+                for (col, text, _) in segment.cells() {
+                    cols.push((segment.col + col, text.to_string()));
+                }
+            } else {
+                cols.push((segment.col, segment.text.clone()));
+            }
+        }
+        cols
+    }
+
+    #[test]
+    fn chars_stay_on_their_columns() {
+        let mut row = row(true);
+        row.push("f", 0, 1, run(1, black()));
+        row.push("n", 1, 1, run(1, white()));
+        row.push("漢", 2, 2, run(3, black()));
+        row.push("-", 4, 1, run(1, black()));
+        row.push(">", 5, 1, run(1, black()));
+        row.push(" ", 6, 4, run(1, black()));
+
+        assert_eq!(row.segments.len(), 4);
+        assert_eq!(
+            painted_cols(&row),
+            [
+                (0, "f".to_string()),
+                (1, "n".to_string()),
+                (2, "漢".to_string()),
+                (4, "-".to_string()),
+                (5, ">".to_string()),
+                (6, " ".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn cells_keep_the_style_of_their_run() {
+        let mut row = row(true);
+        row.push("a", 0, 1, run(1, black()));
+        row.push("b", 1, 1, run(1, white()));
+        row.push("c", 2, 1, run(1, white()));
+
+        let cells = row.segments[0].cells();
+        let colors: Vec<_> = cells.iter().map(|(_, _, run)| run.color).collect();
+        assert_eq!(colors, [black(), white(), white()]);
+        assert!(cells.iter().all(|(_, _, run)| run.len == 1));
+    }
+
+    #[test]
+    fn letter_spacing_keeps_graphemes_apart() {
+        let mut row = row(false);
+        row.push("a", 0, 1, run(1, black()));
+        row.push("b", 1, 1, run(1, black()));
+
+        assert_eq!(row.segments.len(), 2);
+        assert_eq!(
+            painted_cols(&row),
+            [(0, "a".to_string()), (1, "b".to_string())]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use gpui::*;
+use helix_core::syntax::LanguageServerFeature;
+use helix_lsp::{lsp, util::pos_to_lsp_pos};
+use helix_term::commands;
+use helix_term::compositor::{self, Component, Compositor, EventResult};
+use helix_term::job::dispatch;
+use helix_view::input::Event;
+use helix_view::{key, Editor, ViewId};
+
+use crate::markdown::Markdown;
+use crate::utils::color_to_hsla;
+
+/// Requests hover documentation for `char_idx` in the document of `view_id`.
+pub fn request_hover(editor: &mut Editor, view_id: ViewId, char_idx: usize) {
+    let Some(view) = editor.tree.try_get(view_id) else {
+        return;
+    };
+    let Some(doc) = editor.document(view.doc) else {
+        return;
+    };
+
+    // TODO: merge multiple language server hover results into one instead of
+    // just taking the first language server that supports it
+    let future = doc
+        .language_servers_with_feature(LanguageServerFeature::Hover)
+        .find_map(|language_server| {
+            let pos = pos_to_lsp_pos(doc.text(), char_idx, language_server.offset_encoding());
+            language_server.text_document_hover(doc.identifier(), pos, None)
+        });
+
+    let Some(future) = future else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        match future.await {
+            Ok(json) => {
+                let response = match serde_json::from_value::<Option<lsp::Hover>>(json) {
+                    Ok(response) => response,
+                    Err(err) => {
+                        log::error!("invalid hover response: {err}");
+                        return;
+                    }
+                };
+                dispatch(move |editor, compositor| {
+                    show_hover(editor, compositor, view_id, char_idx, response)
+                })
+                .await
+            }
+            Err(err) => log::error!("hover request failed: {err}"),
+        }
+    });
+}
+
+// mostly copy pasted from helix_term/src/commands/lsp.rs
+fn marked_string_to_markdown(contents: lsp::MarkedString) -> String {
+    match contents {
+        lsp::MarkedString::String(contents) => contents,
+        lsp::MarkedString::LanguageString(string) => {
+            if string.language == "markdown" {
+                string.value
+            } else {
+                format!("```{}\n{}\n```", string.language, string.value)
+            }
+        }
+    }
+}
+
+fn show_hover(
+    editor: &mut Editor,
+    compositor: &mut Compositor,
+    view_id: ViewId,
+    anchor: usize,
+    response: Option<lsp::Hover>,
+) {
+    let Some(hover) = response else {
+        compositor.remove(Hover::ID);
+        return;
+    };
+
+    let contents = match hover.contents {
+        lsp::HoverContents::Scalar(contents) => marked_string_to_markdown(contents),
+        lsp::HoverContents::Array(contents) => contents
+            .into_iter()
+            .map(marked_string_to_markdown)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        lsp::HoverContents::Markup(contents) => contents.value,
+    };
+
+    let markdown = Markdown::parse(&contents, editor);
+    if markdown.is_empty() {
+        compositor.remove(Hover::ID);
+        return;
+    }
+
+    compositor.replace_or_push(
+        Hover::ID,
+        Hover {
+            markdown,
+            view_id,
+            anchor,
+        },
+    );
+}
+
+/// Native replacement of helix's `hover` command, bound in the keymap.
+pub fn hover(cx: &mut commands::Context) {
+    let editor = &mut *cx.editor;
+    let view_id = editor.tree.focus;
+    let cursor = {
+        let view = editor.tree.get(view_id);
+        let doc = &editor.documents[&view.doc];
+        doc.selection(view_id)
+            .primary()
+            .cursor(doc.text().slice(..))
+    };
+    request_hover(editor, view_id, cursor);
+}
+
+/// Hover documentation, lives in the compositor so that it gets closed on
+/// key presses. It is rendered natively by the document view.
+pub struct Hover {
+    markdown: Markdown,
+    view_id: ViewId,
+    // char the documentation is about
+    anchor: usize,
+}
+
+impl Hover {
+    pub const ID: &'static str = "native-hover";
+
+    /// Snapshot of the popup for rendering.
+    pub fn popup(&self, editor: &Editor) -> HoverPopup {
+        let theme = &editor.theme;
+        let fg = theme
+            .get("ui.text")
+            .fg
+            .and_then(color_to_hsla)
+            .unwrap_or(white());
+        let bg = theme
+            .get("ui.popup")
+            .bg
+            .and_then(color_to_hsla)
+            .unwrap_or(black());
+
+        HoverPopup {
+            view_id: self.view_id,
+            anchor: self.anchor,
+            markdown: self.markdown.clone(),
+            fg,
+            bg,
+        }
+    }
+}
+
+impl Component for Hover {
+    fn handle_event(&mut self, event: &Event, _cx: &mut compositor::Context) -> EventResult {
+        let Event::Key(event) = event else {
+            return EventResult::Ignored(None);
+        };
+
+        let close = Box::new(|compositor: &mut Compositor, _: &mut compositor::Context| {
+            compositor.remove(Self::ID);
+        });
+
+        match *event {
+            key!(Esc) => EventResult::Consumed(Some(close)),
+            // like helix's popups, let the editor handle the key too
+            _ => EventResult::Ignored(Some(close)),
+        }
+    }
+
+    fn render(
+        &mut self,
+        _area: helix_view::graphics::Rect,
+        _frame: &mut tui::buffer::Buffer,
+        _ctx: &mut compositor::Context,
+    ) {
+        // rendered by the document view
+    }
+
+    fn id(&self) -> Option<&'static str> {
+        Some(Self::ID)
+    }
+}
+
+#[derive(Debug, Clone, IntoElement)]
+pub struct HoverPopup {
+    pub view_id: ViewId,
+    pub anchor: usize,
+    markdown: Markdown,
+    fg: Hsla,
+    bg: Hsla,
+}
+
+impl RenderOnce for HoverPopup {
+    fn render(self, cx: &mut WindowContext) -> impl IntoElement {
+        let font = cx.global::<crate::FontSettings>().fixed_font.clone();
+        let style = cx.global::<crate::FontSettings>().ui_text_style();
+
+        div()
+            .id("hover")
+            .max_w(px(600.))
+            .max_h(px(300.))
+            .overflow_y_scroll()
+            .p_2()
+            .shadow_sm()
+            .rounded_sm()
+            .font(font)
+            .text_size(style.font_size)
+            .line_height(style.line_height)
+            .text_color(self.fg)
+            .bg(self.bg)
+            .child(self.markdown)
+    }
+}
@@ -54,6 +54,7 @@ impl EventEmitter<DismissEvent> for InfoBoxView {}
 impl Render for InfoBoxView {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
         let font = cx.global::<crate::FontSettings>().fixed_font.clone();
+        let style = cx.global::<crate::FontSettings>().ui_text_style();
 
         div()
             .track_focus(&self.focus)
@@ -71,7 +72,8 @@ impl Render for InfoBoxView {
                     .rounded_sm()
                     .shadow_sm()
                     .font(font)
-                    .text_size(px(12.))
+                    .text_size(style.font_size)
+                    .line_height(style.line_height)
                     .text_color(self.style.text.color.unwrap())
                     .bg(self.style.background.as_ref().cloned().unwrap())
                     .p_2()
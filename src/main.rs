@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::task::Poll;
 
 use anyhow::{Context, Error, Result};
 use helix_core::diagnostic::Severity;
@@ -6,21 +6,29 @@ use helix_loader::VERSION_AND_GIT_HASH;
 use helix_term::args::Args;
 use helix_term::config::{Config, ConfigLoadError};
 
+use config::GuiConfig;
+
 use gpui::{
-    actions, App, AppContext, Context as _, Menu, MenuItem, TitlebarOptions, VisualContext as _,
-    WindowBackgroundAppearance, WindowKind, WindowOptions,
+    actions, App, AppContext, Context as _, KeyBinding, Menu, MenuItem, TitlebarOptions,
+    VisualContext as _, WindowBackgroundAppearance, WindowKind, WindowOptions,
 };
 
 pub use application::Input;
 use application::{Application, InputEvent};
 
 mod application;
+mod code_action;
+mod commands;
+mod completion;
+mod config;
 mod document;
+mod hover;
 mod info_box;
+mod markdown;
 mod notification;
 mod overlay;
 mod picker;
-mod prompt;
+mod signature_help;
 mod statusline;
 mod utils;
 mod workspace;
@@ -60,9 +68,9 @@ fn main() -> Result<()> {
     let rt = tokio::runtime::Runtime::new().unwrap();
     let handle = rt.handle();
     let _guard = handle.enter();
-    let app = init_editor().unwrap().unwrap();
+    let (app, gui_config) = init_editor().unwrap().unwrap();
     drop(_guard);
-    gui_main(app, handle.clone());
+    gui_main(app, gui_config, handle.clone());
     Ok(())
 }
 
@@ -101,7 +109,13 @@ actions!(
         Minimize,
         MinimizeAll,
         Zoom,
-        Tutor
+        Tutor,
+        GrowSplit,
+        ShrinkSplit,
+        EqualizeSplits,
+        ZoomIn,
+        ZoomOut,
+        ResetZoom
     ]
 );
 
@@ -137,12 +151,24 @@ fn app_menus() -> Vec<Menu<'static>> {
                 MenuItem::action("Paste", Paste),
             ],
         },
+        Menu {
+            name: "View",
+            items: vec![
+                MenuItem::action("Zoom In", ZoomIn),
+                MenuItem::action("Zoom Out", ZoomOut),
+                MenuItem::action("Reset Zoom", ResetZoom),
+            ],
+        },
         Menu {
             name: "Window",
             items: vec![
                 MenuItem::action("Minimize", Minimize),
                 MenuItem::action("Minimize All", MinimizeAll),
                 MenuItem::action("Zoom", Zoom),
+                MenuItem::separator(),
+                MenuItem::action("Grow Split", GrowSplit),
+                MenuItem::action("Shrink Split", ShrinkSplit),
+                MenuItem::action("Equalize Splits", EqualizeSplits),
             ],
         },
         Menu {
@@ -161,49 +187,113 @@ pub struct EditorStatus {
 #[derive(Debug)]
 pub enum Update {
     Redraw,
-    Prompt(prompt::Prompt),
-    Picker(picker::Picker),
+    Overlay(Option<overlay::Layers>),
     Info(helix_view::info::Info),
     EditorEvent(helix_view::editor::EditorEvent),
     EditorStatus(EditorStatus),
+    TextZoom(TextZoom),
+}
+
+/// Change of the size of all text, from the zoom actions or typable commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextZoom {
+    In,
+    Out,
+    Reset,
 }
 
 impl gpui::EventEmitter<Update> for Application {}
 
+/// Change of the text scale with every zoom step.
+const ZOOM_STEP: f32 = 0.1;
+
+/// Fonts and text sizes of the window, made from the `[gui]` settings
+/// and scaled by the zoom level.
 struct FontSettings {
+    /// Zoom steps away from the configured sizes, text is smaller below zero.
+    zoom_steps: i32,
     fixed_font: gpui::Font,
     var_font: gpui::Font,
+    /// Size and relative line height of documents.
+    font_size: gpui::Pixels,
+    line_height: f32,
+    /// Space added to every cell of documents.
+    letter_spacing: gpui::Pixels,
+    /// Size and relative line height of popups, prompts and the top bar.
+    ui_font_size: gpui::Pixels,
+    ui_line_height: f32,
+    /// Space between the edges of the window and its content.
+    padding: gpui::Pixels,
 }
 
 impl gpui::Global for FontSettings {}
 
-fn gui_main(app: Application, handle: tokio::runtime::Handle) {
+impl FontSettings {
+    fn new(config: &GuiConfig, zoom_steps: i32) -> Self {
+        let zoom = 1. + zoom_steps as f32 * ZOOM_STEP;
+        let font = |config: &config::FontConfig, family: &str| gpui::Font {
+            weight: gpui::FontWeight(config.weight.unwrap_or(400.)),
+            features: config.font_features(),
+            ..gpui::font(config.family.clone().unwrap_or_else(|| family.to_string()))
+        };
+        Self {
+            zoom_steps,
+            fixed_font: font(&config.font, "JetBrains Mono"),
+            var_font: font(&config.ui_font, "SF Pro"),
+            font_size: gpui::px(config.font.size.unwrap_or(14.) * zoom),
+            // gpui's default
+            line_height: config.font.line_height.unwrap_or(1.618034),
+            letter_spacing: gpui::px(config.font.letter_spacing.unwrap_or(0.) * zoom),
+            ui_font_size: gpui::px(config.ui_font.size.unwrap_or(12.) * zoom),
+            ui_line_height: config.ui_font.line_height.unwrap_or(1.3),
+            padding: gpui::px(config.padding),
+        }
+    }
+
+    /// Style of document text.
+    fn document_style(&self) -> gpui::TextStyle {
+        gpui::TextStyle {
+            font_family: self.fixed_font.family.clone(),
+            font_features: self.fixed_font.features.clone(),
+            font_weight: self.fixed_font.weight,
+            font_size: self.font_size.into(),
+            line_height: gpui::relative(self.line_height),
+            ..Default::default()
+        }
+    }
+
+    /// Style of monospace text in popups and prompts.
+    fn ui_text_style(&self) -> gpui::TextStyle {
+        gpui::TextStyle {
+            font_family: self.fixed_font.family.clone(),
+            font_features: self.fixed_font.features.clone(),
+            font_weight: self.fixed_font.weight,
+            font_size: self.ui_font_size.into(),
+            line_height: gpui::relative(self.ui_line_height),
+            ..Default::default()
+        }
+    }
+}
+
+fn gui_main(app: Application, gui_config: GuiConfig, handle: tokio::runtime::Handle) {
     App::new().run(|cx: &mut AppContext| {
         let options = window_options(cx);
+        cx.bind_keys([
+            KeyBinding::new("ctrl-=", ZoomIn, None),
+            KeyBinding::new("ctrl-+", ZoomIn, None),
+            KeyBinding::new("ctrl--", ZoomOut, None),
+            KeyBinding::new("ctrl-0", ResetZoom, None),
+            KeyBinding::new("ctrl-alt-=", GrowSplit, None),
+            KeyBinding::new("ctrl-alt--", ShrinkSplit, None),
+            KeyBinding::new("ctrl-alt-0", EqualizeSplits, None),
+        ]);
 
         cx.open_window(options, |cx| {
             let input = cx.new_model(|_| crate::application::Input);
-            let crank = cx.new_model(|mc| {
-                mc.spawn(|crank, mut cx| async move {
-                    loop {
-                        cx.background_executor()
-                            .timer(Duration::from_millis(50))
-                            .await;
-                        let _ = crank.update(&mut cx, |_crank, cx| {
-                            cx.emit(());
-                        });
-                    }
-                })
-                .detach();
-                crate::application::Crank
-            });
-            let crank_1 = crank.clone();
-            std::mem::forget(crank_1);
 
             let input_1 = input.clone();
             let handle_1 = handle.clone();
             let app = cx.new_model(move |mc| {
-                let handle_1 = handle_1.clone();
                 let handle_2 = handle_1.clone();
                 mc.subscribe(
                     &input_1.clone(),
@@ -212,8 +302,17 @@ fn gui_main(app: Application, handle: tokio::runtime::Handle) {
                     },
                 )
                 .detach();
-                mc.subscribe(&crank, move |this: &mut Application, _, ev, cx| {
-                    this.handle_crank_event(*ev, cx, handle_2.clone());
+                mc.spawn(|this, mut cx| async move {
+                    std::future::poll_fn(|task_cx| {
+                        match this
+                            .update(&mut cx, |this, cx| this.poll_step(task_cx, cx, &handle_2))
+                        {
+                            Ok(()) => Poll::Pending,
+                            // application is gone
+                            Err(_) => Poll::Ready(()),
+                        }
+                    })
+                    .await
                 })
                 .detach();
                 app
@@ -222,25 +321,33 @@ fn gui_main(app: Application, handle: tokio::runtime::Handle) {
             cx.activate(true);
             cx.set_menus(app_menus());
 
-            let font_settings = FontSettings {
-                fixed_font: gpui::font("JetBrains Mono"),
-                var_font: gpui::font("SF Pro"),
-            };
-            cx.set_global(font_settings);
+            let zoom = gui_config
+                .remember_zoom
+                .then(config::load_zoom)
+                .flatten()
+                .unwrap_or(0);
+            cx.set_global(FontSettings::new(&gui_config, zoom));
+            cx.set_global(gui_config);
 
             let input_1 = input.clone();
-            cx.new_view(|cx| {
+            let app_1 = app.clone();
+            let workspace = cx.new_view(|cx| {
                 cx.subscribe(&app, |w: &mut workspace::Workspace, _, ev, cx| {
                     w.handle_event(ev, cx);
                 })
                 .detach();
                 workspace::Workspace::new(app, input_1.clone(), handle, cx)
-            })
+            });
+
+            // layers pushed during init (e.g. file picker for a directory argument)
+            // need to be announced once everyone is subscribed
+            app_1.update(cx, |app, cx| app.emit_overlays(cx));
+            workspace
         });
     })
 }
 
-fn init_editor() -> Result<Option<Application>> {
+fn init_editor() -> Result<Option<(Application, GuiConfig)>> {
     let help = format!(
         "\
 {} {}
@@ -331,10 +438,10 @@ FLAGS:
         helix_stdx::env::set_current_working_dir(path)?;
     }
 
-    let config = match Config::load_default() {
+    let (config, gui_config) = match config::load_default() {
         Ok(config) => config,
         Err(ConfigLoadError::Error(err)) if err.kind() == std::io::ErrorKind::NotFound => {
-            Config::default()
+            (Config::default(), GuiConfig::default())
         }
         Err(ConfigLoadError::Error(err)) => return Err(Error::new(err)),
         Err(ConfigLoadError::BadConfig(err)) => {
@@ -342,10 +449,12 @@ FLAGS:
             eprintln!("Press <ENTER> to continue with default config");
             use std::io::Read;
             let _ = std::io::stdin().read(&mut []);
-            Config::default()
+            (Config::default(), GuiConfig::default())
         }
     };
 
+    utils::init_palette(&gui_config.palette);
+
     let lang_loader = helix_core::config::user_lang_loader().unwrap_or_else(|err| {
         eprintln!("{}", err);
         eprintln!("Press <ENTER> to continue with default language config");
@@ -359,5 +468,5 @@ FLAGS:
     let app = application::init_editor(args, config, lang_loader)
         .context("unable to create new application")?;
 
-    Ok(Some(app))
+    Ok(Some((app, gui_config)))
 }
@@ -0,0 +1,357 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use gpui::prelude::FluentBuilder;
+use gpui::*;
+use helix_core::syntax::{self, HighlightEvent, InjectionLanguageMarker, Syntax};
+use helix_core::Rope;
+use helix_view::graphics::{Modifier, Style, UnderlineStyle};
+use helix_view::Theme;
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+use crate::utils::{color_to_hsla, style_to_highlight, TextWithStyle};
+
+#[derive(Debug, Clone)]
+enum BlockKind {
+    Heading(HeadingLevel),
+    Paragraph,
+    Code,
+    Rule,
+}
+
+#[derive(Debug, Clone)]
+struct Block {
+    kind: BlockKind,
+    text: TextWithStyle,
+    /// byte ranges of the links in `text` and their destinations
+    links: Vec<(Range<usize>, String)>,
+    /// nesting level of the list the block is in
+    indent: usize,
+}
+
+/// Markdown parsed into styled blocks, code blocks are highlighted
+/// with the tree-sitter grammars of the editor.
+#[derive(Debug, Clone, IntoElement)]
+pub struct Markdown {
+    blocks: Vec<Block>,
+    fg: Hsla,
+    code_bg: Option<Hsla>,
+    rule_color: Hsla,
+}
+
+impl Markdown {
+    pub fn parse(contents: &str, editor: &helix_view::Editor) -> Self {
+        let theme = &editor.theme;
+        let mut builder = Builder {
+            theme,
+            loader: editor.syn_loader.clone(),
+            blocks: Vec::new(),
+            text: String::new(),
+            highlights: Vec::new(),
+            links: Vec::new(),
+            styles: Vec::new(),
+            link: None,
+            code: None,
+            lists: Vec::new(),
+        };
+        let options = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+        for event in Parser::new_ext(contents, options) {
+            builder.push(event);
+        }
+        builder.flush(BlockKind::Paragraph);
+
+        let fg = theme
+            .get("ui.text")
+            .fg
+            .and_then(color_to_hsla)
+            .unwrap_or(white());
+        Self {
+            blocks: builder.blocks,
+            fg,
+            code_bg: theme.get("markup.raw.block").bg.and_then(color_to_hsla),
+            rule_color: theme
+                .get("ui.window")
+                .fg
+                .and_then(color_to_hsla)
+                .unwrap_or(fg),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+struct Builder<'a> {
+    theme: &'a Theme,
+    loader: Arc<ArcSwap<syntax::Loader>>,
+    blocks: Vec<Block>,
+    // the block being built
+    text: String,
+    highlights: Vec<(Range<usize>, HighlightStyle)>,
+    links: Vec<(Range<usize>, String)>,
+    // styles of the enclosing inline tags
+    styles: Vec<Style>,
+    // start and destination of the link being built
+    link: Option<(usize, String)>,
+    // language and source of the code block being built
+    code: Option<(Option<String>, String)>,
+    // next number of each enclosing list, `None` for bullet lists
+    lists: Vec<Option<u64>>,
+}
+
+impl<'a> Builder<'a> {
+    fn push(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => {
+                if let Some((_, code)) = &mut self.code {
+                    code.push_str(&text);
+                } else {
+                    self.push_text(&text, None);
+                }
+            }
+            Event::Code(text) => {
+                let style = self.theme.get("markup.raw.inline");
+                self.push_text(&text, Some(style));
+            }
+            Event::Html(text) | Event::InlineHtml(text) => self.push_text(&text, None),
+            Event::SoftBreak => self.push_text(" ", None),
+            Event::HardBreak => self.push_text("\n", None),
+            Event::Rule => {
+                self.flush(BlockKind::Paragraph);
+                self.blocks.push(Block {
+                    kind: BlockKind::Rule,
+                    text: TextWithStyle::new("", Vec::new()),
+                    links: Vec::new(),
+                    indent: 0,
+                });
+            }
+            Event::TaskListMarker(checked) => {
+                self.push_text(if checked { "[x] " } else { "[ ] " }, None)
+            }
+            Event::FootnoteReference(label) => self.push_text(&format!("[{}]", label), None),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading { level, .. } => {
+                self.flush(BlockKind::Paragraph);
+                let scope = format!("markup.heading.{}", level as usize);
+                let style = self
+                    .theme
+                    .try_get(&scope)
+                    .unwrap_or_else(|| self.theme.get("markup.heading"));
+                self.styles.push(style.add_modifier(Modifier::BOLD));
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush(BlockKind::Paragraph);
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .next()
+                        .filter(|language| !language.is_empty())
+                        .map(String::from),
+                    CodeBlockKind::Indented => None,
+                };
+                self.code = Some((language, String::new()));
+            }
+            Tag::List(start) => {
+                self.flush(BlockKind::Paragraph);
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush(BlockKind::Paragraph);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                let style = self.theme.get("markup.list");
+                self.push_text(&marker, Some(style));
+            }
+            Tag::Emphasis => self
+                .styles
+                .push(Style::default().add_modifier(Modifier::ITALIC)),
+            Tag::Strong => self
+                .styles
+                .push(Style::default().add_modifier(Modifier::BOLD)),
+            Tag::Strikethrough => self
+                .styles
+                .push(Style::default().add_modifier(Modifier::CROSSED_OUT)),
+            Tag::Link { dest_url, .. } => {
+                let style = self
+                    .theme
+                    .get("markup.link.text")
+                    .underline_style(UnderlineStyle::Line);
+                self.styles.push(style);
+                self.link = Some((self.text.len(), dest_url.to_string()));
+            }
+            Tag::BlockQuote => self.styles.push(self.theme.get("markup.quote")),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::Item => self.flush(BlockKind::Paragraph),
+            TagEnd::Heading(level) => {
+                self.styles.pop();
+                self.flush(BlockKind::Heading(level));
+            }
+            TagEnd::CodeBlock => {
+                if let Some((language, code)) = self.code.take() {
+                    let text = self.highlight_code(code.trim_end(), language.as_deref());
+                    self.blocks.push(Block {
+                        kind: BlockKind::Code,
+                        text,
+                        links: Vec::new(),
+                        indent: self.lists.len(),
+                    });
+                }
+            }
+            TagEnd::List(_) => {
+                self.flush(BlockKind::Paragraph);
+                self.lists.pop();
+            }
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough | TagEnd::BlockQuote => {
+                self.styles.pop();
+            }
+            TagEnd::Link => {
+                self.styles.pop();
+                if let Some((start, url)) = self.link.take() {
+                    self.links.push((start..self.text.len(), url));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push_text(&mut self, text: &str, style: Option<Style>) {
+        let style = self
+            .styles
+            .iter()
+            .chain(style.iter())
+            .fold(Style::default(), |acc, style| acc.patch(*style));
+        let start = self.text.len();
+        self.text.push_str(text);
+        self.highlights
+            .push((start..self.text.len(), style_to_highlight(style)));
+    }
+
+    // finishes the block being built, if there is one
+    fn flush(&mut self, kind: BlockKind) {
+        if self.text.trim().is_empty() {
+            self.text.clear();
+            self.highlights.clear();
+            self.links.clear();
+            return;
+        }
+        let text = std::mem::take(&mut self.text);
+        let len = text.trim_end().len();
+        let highlights = std::mem::take(&mut self.highlights)
+            .into_iter()
+            .filter(|(range, _)| range.start < len)
+            .map(|(range, style)| (range.start..range.end.min(len), style))
+            .collect();
+        self.blocks.push(Block {
+            kind,
+            text: TextWithStyle::new(text[..len].to_string(), highlights),
+            links: std::mem::take(&mut self.links),
+            indent: self.lists.len().saturating_sub(1),
+        });
+    }
+
+    // mostly copy pasted from helix_term/src/ui/markdown.rs
+    fn highlight_code(&self, code: &str, language: Option<&str>) -> TextWithStyle {
+        let text_style = self.theme.get("markup.raw.block");
+        let rope = Rope::from(code);
+        let syntax = language
+            .and_then(|language| {
+                self.loader
+                    .load()
+                    .language_configuration_for_injection_string(&InjectionLanguageMarker::Name(
+                        language.into(),
+                    ))
+            })
+            .and_then(|config| config.highlight_config(&self.loader.load().scopes()))
+            .and_then(|config| Syntax::new(rope.slice(..), config, self.loader.clone()));
+
+        let Some(syntax) = syntax else {
+            let highlights = vec![(0..code.len(), style_to_highlight(text_style))];
+            return TextWithStyle::new(code.to_string(), highlights);
+        };
+
+        let mut highlights = Vec::new();
+        let mut active = Vec::new();
+        for event in syntax.highlight_iter(rope.slice(..), None, None) {
+            let Ok(event) = event else {
+                break;
+            };
+            match event {
+                HighlightEvent::HighlightStart(highlight) => active.push(highlight),
+                HighlightEvent::HighlightEnd => {
+                    active.pop();
+                }
+                HighlightEvent::Source { start, end } => {
+                    let style = active.iter().fold(text_style, |acc, highlight| {
+                        acc.patch(self.theme.highlight(highlight.0))
+                    });
+                    highlights.push((start..end, style_to_highlight(style)));
+                }
+            }
+        }
+        TextWithStyle::new(code.to_string(), highlights)
+    }
+}
+
+impl RenderOnce for Markdown {
+    fn render(self, cx: &mut WindowContext) -> impl IntoElement {
+        let font_size = cx.global::<crate::FontSettings>().ui_font_size;
+        let mut default_style = cx.global::<crate::FontSettings>().ui_text_style();
+        default_style.color = self.fg;
+
+        let blocks = self.blocks.into_iter().enumerate().map(|(idx, block)| {
+            let text = block.text.into_styled_text(&default_style);
+            let container = div().pl(px(12.) * block.indent as f32);
+            match block.kind {
+                BlockKind::Heading(level) => {
+                    let size = match level {
+                        HeadingLevel::H1 => font_size * (4. / 3.),
+                        HeadingLevel::H2 => font_size * (7. / 6.),
+                        _ => font_size * (13. / 12.),
+                    };
+                    container.text_size(size).child(text)
+                }
+                BlockKind::Code => container.child(
+                    div()
+                        .p_1()
+                        .rounded_sm()
+                        .when_some(self.code_bg, |this, bg| this.bg(bg))
+                        .child(text),
+                ),
+                BlockKind::Rule => container.child(div().h(px(1.)).bg(self.rule_color)),
+                BlockKind::Paragraph if block.links.is_empty() => container.child(text),
+                BlockKind::Paragraph => {
+                    let (ranges, urls): (Vec<_>, Vec<_>) = block.links.into_iter().unzip();
+                    let text = InteractiveText::new(("markdown-block", idx), text).on_click(
+                        ranges,
+                        move |link, _cx| {
+                            if let Err(err) = open::that_detached(&urls[link]) {
+                                log::error!("failed to open {}: {}", urls[link], err);
+                            }
+                        },
+                    );
+                    container.child(text)
+                }
+            }
+        });
+
+        div().flex().flex_col().gap_2().children(blocks)
+    }
+}
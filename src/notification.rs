@@ -257,6 +257,7 @@ impl Render for NotificationView {
 impl RenderOnce for Notification {
     fn render(mut self, cx: &mut WindowContext) -> impl IntoElement {
         let message = self.message.take();
+        let style = cx.global::<crate::FontSettings>().ui_text_style();
         div()
             .flex()
             .flex_col()
@@ -269,7 +270,8 @@ impl RenderOnce for Notification {
             .shadow_sm()
             .rounded_sm()
             .font(cx.global::<crate::FontSettings>().fixed_font.clone())
-            .text_size(px(12.))
+            .text_size(style.font_size)
+            .line_height(style.line_height)
             .child(
                 div()
                     .flex()
@@ -1,26 +1,117 @@
+use std::ops::Range;
+
 use gpui::prelude::FluentBuilder;
 use gpui::*;
+use helix_term::compositor::Compositor;
+use helix_view::graphics::{CursorKind, Rect};
+use helix_view::key;
+use tui::buffer::{Buffer, Cell};
+
+use crate::document::DocumentElement;
+use crate::picker::Picker;
+use crate::utils::{color_to_hsla, TextWithStyle};
+use crate::workspace::TOP_BAR_HEIGHT;
+use crate::{Core, Input, InputEvent};
+
+/// Compositor layers (pickers, prompts, popups, menus...) rendered through
+/// helix's own TUI renderer and cropped to the cells they draw.
+#[derive(Debug, Clone)]
+pub struct Layers {
+    text: TextWithStyle,
+    // part of the compositor area covered by the layers, in cells
+    bounds: Rect,
+}
+
+impl Layers {
+    pub fn make(
+        editor: &mut helix_view::Editor,
+        compositor: &mut Compositor,
+        jobs: &mut helix_term::job::Jobs,
+    ) -> Option<Self> {
+        let area = compositor.size();
+        let mut buf = Buffer::empty(area);
 
-use crate::picker::{Picker, PickerElement};
-use crate::prompt::{Prompt, PromptElement};
+        let mut comp_ctx = helix_term::compositor::Context {
+            editor,
+            scroll: None,
+            jobs,
+        };
+        compositor.render(area, &mut buf, &mut comp_ctx);
+
+        // the cursor is drawn by the terminal in the TUI, paint it into the cell instead
+        if let (Some(pos), kind) = compositor.cursor(area, comp_ctx.editor) {
+            let (x, y) = (pos.col as u16, pos.row as u16);
+            if kind != CursorKind::Hidden && x < area.right() && y < area.bottom() {
+                let style = comp_ctx.editor.theme.get("ui.cursor.primary");
+                buf[(x, y)].set_style(style);
+            }
+        }
+
+        let bounds = Self::drawn_bounds(&buf)?;
+        let mut cropped = Buffer::empty(Rect::new(0, 0, bounds.width, bounds.height));
+        for y in 0..bounds.height {
+            for x in 0..bounds.width {
+                cropped[(x, y)] = buf[(bounds.x + x, bounds.y + y)].clone();
+            }
+        }
+
+        Some(Self {
+            text: TextWithStyle::from_buffer(cropped),
+            bounds,
+        })
+    }
+
+    // smallest rectangle containing every cell that was drawn to
+    fn drawn_bounds(buf: &Buffer) -> Option<Rect> {
+        let blank = Cell::default();
+        let area = buf.area;
+        let (mut left, mut top) = (u16::MAX, u16::MAX);
+        let (mut right, mut bottom) = (0, 0);
+
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                if buf[(x, y)] != blank {
+                    left = left.min(x);
+                    top = top.min(y);
+                    right = right.max(x + 1);
+                    bottom = bottom.max(y + 1);
+                }
+            }
+        }
+
+        if left >= right || top >= bottom {
+            return None;
+        }
+        Some(Rect::new(left, top, right - left, bottom - top))
+    }
+}
 
 pub struct OverlayView {
-    prompt: Option<Prompt>,
-    picker: Option<Picker>,
+    core: Model<Core>,
+    input: Model<Input>,
+    layers: Option<Layers>,
+    has_picker: bool,
+    picker_scroll: UniformListScrollHandle,
+    // selection the picker list was last scrolled to
+    picker_cursor: Option<u32>,
     focus: FocusHandle,
 }
 
 impl OverlayView {
-    pub fn new(focus: &FocusHandle) -> Self {
+    pub fn new(core: Model<Core>, input: Model<Input>, focus: &FocusHandle) -> Self {
         Self {
-            prompt: None,
-            picker: None,
+            core,
+            input,
+            layers: None,
+            has_picker: false,
+            picker_scroll: UniformListScrollHandle::new(),
+            picker_cursor: None,
             focus: focus.clone(),
         }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.prompt.is_none() && self.picker.is_none()
+        self.layers.is_none() && !self.has_picker
     }
 
     pub fn subscribe(&self, editor: &Model<crate::Core>, cx: &mut ViewContext<Self>) {
@@ -31,18 +122,210 @@ impl OverlayView {
     }
 
     fn handle_event(&mut self, ev: &crate::Update, cx: &mut ViewContext<Self>) {
-        match ev {
-            crate::Update::Prompt(prompt) => {
-                self.prompt = Some(prompt.clone());
-                cx.notify();
+        if let crate::Update::Overlay(layers) = ev {
+            let was_empty = self.is_empty();
+            self.layers = layers.clone();
+            self.has_picker = self.core.update(cx, |core, _| {
+                let has_picker = core.compositor.find_id::<Picker>(Picker::ID).is_some();
+                if !has_picker {
+                    core.picker_previews.clear();
+                }
+                has_picker
+            });
+            if !self.has_picker {
+                self.picker_cursor = None;
             }
-            crate::Update::Picker(picker) => {
-                self.picker = Some(picker.clone());
-                cx.notify();
+            if was_empty && !self.is_empty() {
+                cx.focus(&self.focus);
             }
-            _ => {}
+            cx.notify();
         }
     }
+
+    fn text_style(&self, color: Hsla, cx: &mut ViewContext<Self>) -> TextStyle {
+        let mut style = cx.global::<crate::FontSettings>().ui_text_style();
+        style.color = color;
+        style
+    }
+
+    fn render_picker(&mut self, cx: &mut ViewContext<Self>) -> Option<Div> {
+        let status = self.core.update(cx, |core, _| {
+            let picker = core.compositor.find_id::<Picker>(Picker::ID)?;
+            picker.tick();
+            picker.update_preview(&core.editor, &mut core.picker_previews);
+            Some(picker.status())
+        })?;
+
+        if self.picker_cursor != Some(status.cursor) {
+            self.picker_scroll.scroll_to_item(status.cursor as usize);
+            self.picker_cursor = Some(status.cursor);
+        }
+
+        let (fg, bg, border, has_preview) = {
+            let core = self.core.read(cx);
+            let theme = &core.editor.theme;
+            let fg = theme
+                .get("ui.text")
+                .fg
+                .and_then(color_to_hsla)
+                .unwrap_or(white());
+            let bg = theme
+                .get("ui.popup")
+                .bg
+                .or(theme.get("ui.background").bg)
+                .and_then(color_to_hsla)
+                .unwrap_or(black());
+            let border = theme
+                .get("ui.window")
+                .fg
+                .and_then(color_to_hsla)
+                .unwrap_or(fg);
+            (fg, bg, border, core.picker_previews.shown().is_some())
+        };
+
+        let cursor = status.cursor;
+        let list = uniform_list(
+            cx.view().clone(),
+            "picker-items",
+            status.matched_count as usize,
+            move |this, range, cx| this.picker_rows(range, cursor, cx),
+        )
+        .track_scroll(self.picker_scroll.clone())
+        .flex_grow();
+
+        let prompt = div()
+            .flex()
+            .flex_row()
+            .justify_between()
+            .px_2()
+            .py_1()
+            .border_b_1()
+            .border_color(border)
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .child(format!("> {}", status.query))
+                    .child(div().w(px(2.)).h(px(14.)).bg(fg)),
+            )
+            .child(format!("{}/{}", status.matched_count, status.item_count));
+
+        let style = self.text_style(fg, cx);
+        let preview = status.show_preview.then(|| {
+            div()
+                .flex()
+                .w(relative(0.5))
+                .h_full()
+                .border_l_1()
+                .border_color(border)
+                .when(has_preview, |this| {
+                    this.child(DocumentElement::preview(
+                        self.core.clone(),
+                        style.clone(),
+                        &self.focus,
+                    ))
+                })
+                .when(!has_preview, |this| {
+                    this.items_center().justify_center().child("No preview")
+                })
+        });
+
+        let picker = div()
+            .track_focus(&self.focus)
+            .flex()
+            .flex_row()
+            .w(relative(0.9))
+            .h(relative(0.9))
+            .overflow_hidden()
+            .bg(bg)
+            .text_color(fg)
+            .border_1()
+            .border_color(border)
+            .shadow_sm()
+            .rounded_sm()
+            .font(cx.global::<crate::FontSettings>().fixed_font.clone())
+            .text_size(style.font_size)
+            .line_height(style.line_height)
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .h_full()
+                    .when(status.show_preview, |this| this.w(relative(0.5)))
+                    .when(!status.show_preview, |this| this.w_full())
+                    .child(prompt)
+                    .child(list),
+            )
+            .when_some(preview, |this, preview| this.child(preview));
+
+        Some(
+            div()
+                .absolute()
+                .size_full()
+                .flex()
+                .justify_center()
+                .items_center()
+                .child(picker),
+        )
+    }
+
+    // only the visible part of the list gets formatted
+    fn picker_rows(
+        &mut self,
+        range: Range<usize>,
+        cursor: u32,
+        cx: &mut ViewContext<Self>,
+    ) -> Vec<Stateful<Div>> {
+        let (rows, fg, selected_bg) = self.core.update(cx, |core, _| {
+            let theme = &core.editor.theme;
+            let fg = theme
+                .get("ui.text")
+                .fg
+                .and_then(color_to_hsla)
+                .unwrap_or(white());
+            let selected_bg = theme.get("ui.menu.selected").bg.and_then(color_to_hsla);
+            let rows = core
+                .compositor
+                .find_id::<Picker>(Picker::ID)
+                .map(|picker| picker.rows(range.start as u32..range.end as u32, &core.editor))
+                .unwrap_or_default();
+            (rows, fg, selected_bg)
+        });
+        let style = self.text_style(fg, cx);
+
+        rows.into_iter()
+            .zip(range)
+            .map(|(row, idx)| {
+                div()
+                    .id(idx)
+                    .w_full()
+                    .px_2()
+                    .when(idx == cursor as usize, |this| {
+                        this.when_some(selected_bg, |this, bg| this.bg(bg))
+                    })
+                    .on_click(cx.listener(move |this, ev: &ClickEvent, cx| {
+                        this.picker_click(idx, ev.up.click_count, cx)
+                    }))
+                    .child(row.into_styled_text(&style))
+            })
+            .collect()
+    }
+
+    // click selects, double click opens the item
+    fn picker_click(&mut self, idx: usize, click_count: usize, cx: &mut ViewContext<Self>) {
+        self.core.update(cx, |core, _| {
+            if let Some(picker) = core.compositor.find_id::<Picker>(Picker::ID) {
+                picker.set_cursor(idx as u32);
+            }
+        });
+        if click_count > 1 {
+            self.input.update(cx, |_, cx| {
+                cx.emit(InputEvent::Key(key!(Enter)));
+            });
+        }
+        cx.notify();
+    }
 }
 
 impl FocusableView for OverlayView {
@@ -54,31 +337,54 @@ impl EventEmitter<DismissEvent> for OverlayView {}
 
 impl Render for OverlayView {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
-        println!("rendering overlay");
-        div().absolute().size_full().bottom_0().left_0().child(
-            div()
-                .flex()
-                .h_full()
-                .justify_center()
-                .items_center()
-                .when_some(self.prompt.take(), |this, prompt| {
-                    let handle = cx.focus_handle();
-                    let prompt = PromptElement {
-                        prompt,
-                        focus: handle.clone(),
-                    };
-                    handle.focus(cx);
-                    this.child(prompt)
+        let picker = if self.has_picker {
+            self.render_picker(cx)
+        } else {
+            None
+        };
+
+        div()
+            .absolute()
+            .size_full()
+            .top_0()
+            .left_0()
+            .when_some(picker, |this, picker| this.child(picker))
+            .when_some(self.layers.clone(), |this, layers| {
+                this.child(LayersElement {
+                    layers,
+                    focus: self.focus.clone(),
                 })
-                .when_some(self.picker.take(), |this, picker| {
-                    let handle = cx.focus_handle();
-                    let picker = PickerElement {
-                        picker,
-                        focus: handle.clone(),
-                    };
-                    handle.focus(cx);
-                    this.child(picker)
-                }),
-        )
+            })
+    }
+}
+
+#[derive(IntoElement)]
+pub struct LayersElement {
+    pub layers: Layers,
+    pub focus: FocusHandle,
+}
+
+impl RenderOnce for LayersElement {
+    fn render(self, cx: &mut WindowContext) -> impl IntoElement {
+        let Layers { text, bounds } = self.layers;
+        let fixed_font = cx.global::<crate::FontSettings>().fixed_font.clone();
+        let padding = cx.global::<crate::FontSettings>().padding;
+        // the compositor area is the view tree, so the layers go on its cells
+        let default_style = cx.global::<crate::FontSettings>().document_style();
+        let cell_width = crate::utils::cell_width(&default_style, cx);
+        let line_height = default_style.line_height_in_pixels(cx.rem_size());
+
+        div()
+            .track_focus(&self.focus)
+            .absolute()
+            .left(padding + cell_width * bounds.x as f32)
+            .top(padding + TOP_BAR_HEIGHT + line_height * bounds.y as f32)
+            .shadow_sm()
+            .rounded_sm()
+            .text_color(hsla(1., 1., 1., 1.))
+            .font(fixed_font)
+            .text_size(default_style.font_size)
+            .line_height(default_style.line_height)
+            .child(text.into_styled_text(&default_style))
     }
 }
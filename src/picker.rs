@@ -1,68 +1,738 @@
-use gpui::*;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{hash_map::Entry, HashMap};
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{mpsc, Arc};
 
-use crate::utils::TextWithStyle;
+use futures_util::future::BoxFuture;
+use gpui::{HighlightStyle, SharedString};
+use helix_core::encoding::Encoding;
+use helix_core::movement::Direction;
+use helix_core::unicode::segmentation::UnicodeSegmentation;
+use helix_core::{Rope, Selection};
+use helix_term::commands;
+use helix_term::compositor::{self, Component, EventResult};
+use helix_term::job::dispatch;
+use helix_term::ui::menu::Item;
+use helix_view::editor::Action;
+use helix_view::graphics::{Modifier, Rect, Style};
+use helix_view::input::{Event, KeyEvent};
+use helix_view::keyboard::{KeyCode, KeyModifiers};
+use helix_view::{align_view, ctrl, key, shift, Align, Document, Editor, View};
+use nucleo::pattern::CaseMatching;
+use nucleo::{Config, Nucleo};
+use tui::widgets::Row;
 
+use crate::utils::{style_to_highlight, TextWithStyle};
+
+pub const MAX_FILE_SIZE_FOR_PREVIEW: u64 = 10 * 1024 * 1024;
+
+const PAGE_SIZE: u32 = 10;
+
+/// File shown in the preview pane and the lines to center it on.
+pub type FileLocation = (PathBuf, Option<(usize, usize)>);
+
+pub type FileCallback<T> = Box<dyn Fn(&Editor, &T) -> Option<FileLocation>>;
+type PickerCallback<T> = Box<dyn Fn(&mut commands::Context, &T, Action)>;
+/// Fetches the items matching a query, for pickers asking a language server.
+pub type QueryCallback<T> =
+    Box<dyn Fn(&str, &mut Editor) -> Option<BoxFuture<'static, anyhow::Result<Vec<T>>>>>;
+type QueryFn = Box<dyn Fn(&str, &mut Editor)>;
+
+thread_local! {
+    // the picker closed last, reopened by `last_picker`
+    static LAST_PICKER: RefCell<Option<Box<dyn Component>>> = RefCell::new(None);
+}
+
+/// Takes the picker closed last, if any.
+pub fn take_last_picker() -> Option<Box<dyn Component>> {
+    LAST_PICKER.with(|last| last.borrow_mut().take())
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InjectorShutdown;
+
+/// Adds items to a picker that is already open, e.g. from a directory walker thread.
+pub struct Injector<T: Item> {
+    dst: nucleo::Injector<T>,
+    editor_data: Arc<T::Data>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl<T: Item> Clone for Injector<T> {
+    fn clone(&self) -> Self {
+        Injector {
+            dst: self.dst.clone(),
+            editor_data: self.editor_data.clone(),
+            shutdown: self.shutdown.clone(),
+        }
+    }
+}
+
+impl<T: Item> Injector<T> {
+    pub fn push(&self, item: T) -> Result<(), InjectorShutdown> {
+        if self.shutdown.load(atomic::Ordering::Relaxed) {
+            return Err(InjectorShutdown);
+        }
+
+        inject_item(&self.dst, item, &self.editor_data);
+        Ok(())
+    }
+}
+
+fn inject_item<T: Item>(injector: &nucleo::Injector<T>, item: T, editor_data: &T::Data) {
+    let column = item.filter_text(editor_data).into_owned();
+    injector.push(item, |dst| dst[0] = column.as_str().into());
+}
+
+/// The item type specific half of the picker, erased so that the GUI
+/// can find the picker in the compositor whatever it is picking.
+trait Source {
+    fn tick(&mut self);
+    fn reparse(&mut self, pattern: &str, append: bool);
+    fn matched_count(&self) -> u32;
+    fn item_count(&self) -> u32;
+    fn row(&self, idx: u32, highlight: Style) -> Option<TextWithStyle>;
+    fn location(&self, idx: u32, editor: &Editor) -> Option<FileLocation>;
+    fn accept(&self, idx: u32, cx: &mut commands::Context, action: Action);
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+struct Matcher<T: Item> {
+    nucleo: Nucleo<T>,
+    editor_data: Arc<T::Data>,
+    shutdown: Arc<AtomicBool>,
+    callback_fn: PickerCallback<T>,
+    file_fn: Option<FileCallback<T>>,
+}
+
+impl<T: Item> Matcher<T> {
+    fn replace_items(&mut self, items: Vec<T>) {
+        self.nucleo.restart(false);
+        let injector = self.nucleo.injector();
+        for item in items {
+            inject_item(&injector, item, &self.editor_data);
+        }
+    }
+}
+
+impl<T: Item> Drop for Matcher<T> {
+    fn drop(&mut self) {
+        // ensure we cancel any ongoing background threads streaming into the picker
+        self.shutdown.store(true, atomic::Ordering::Relaxed)
+    }
+}
+
+impl<T: Item> Source for Matcher<T> {
+    fn tick(&mut self) {
+        self.nucleo.tick(10);
+    }
+
+    fn reparse(&mut self, pattern: &str, append: bool) {
+        self.nucleo
+            .pattern
+            .reparse(0, pattern, CaseMatching::Smart, append);
+    }
+
+    fn matched_count(&self) -> u32 {
+        self.nucleo.snapshot().matched_item_count()
+    }
+
+    fn item_count(&self) -> u32 {
+        self.nucleo.snapshot().item_count()
+    }
+
+    fn row(&self, idx: u32, highlight: Style) -> Option<TextWithStyle> {
+        let snapshot = self.nucleo.snapshot();
+        let item = snapshot.get_matched_item(idx)?;
+
+        let mut indices = Vec::new();
+        {
+            let mut matcher = helix_core::fuzzy::MATCHER.lock();
+            matcher.config = Config::DEFAULT;
+            if self.file_fn.is_some() {
+                matcher.config.set_match_paths()
+            }
+            snapshot.pattern().column_pattern(0).indices(
+                item.matcher_columns[0].slice(..),
+                &mut matcher,
+                &mut indices,
+            );
+        }
+        indices.sort_unstable();
+        indices.dedup();
+
+        let row = item.data.format(&self.editor_data);
+        Some(format_row(row, &indices, highlight))
+    }
+
+    fn location(&self, idx: u32, editor: &Editor) -> Option<FileLocation> {
+        let file_fn = self.file_fn.as_ref()?;
+        let item = self.nucleo.snapshot().get_matched_item(idx)?;
+        file_fn(editor, item.data)
+    }
+
+    fn accept(&self, idx: u32, cx: &mut commands::Context, action: Action) {
+        if let Some(item) = self.nucleo.snapshot().get_matched_item(idx) {
+            (self.callback_fn)(cx, item.data, action);
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// Lays the cells out in a single line and marks the graphemes matched by the query.
+fn format_row(row: Row, indices: &[u32], highlight: Style) -> TextWithStyle {
+    let mut text = String::new();
+    let mut highlights: Vec<(Range<usize>, HighlightStyle)> = Vec::new();
+    let mut indices = indices.iter().copied().peekable();
+    let mut grapheme_idx = 0u32;
+
+    for (i, cell) in row.cells.iter().enumerate() {
+        if i > 0 {
+            text.push(' ');
+        }
+        let spans = cell
+            .content
+            .lines
+            .first()
+            .map_or(&[][..], |line| &line.0[..]);
+        for span in spans {
+            // nucleo only considers the first char of a grapheme,
+            // so the indices it returns are grapheme indices
+            for grapheme in span.content.graphemes(true) {
+                let mut style = span.style;
+                if indices.next_if_eq(&grapheme_idx).is_some() {
+                    style = style.patch(highlight);
+                }
+                grapheme_idx += 1;
+
+                let start = text.len();
+                text.push_str(grapheme);
+                let style = style_to_highlight(style);
+                match highlights.last_mut() {
+                    Some((range, last)) if range.end == start && *last == style => {
+                        range.end = text.len()
+                    }
+                    _ => highlights.push((start..text.len(), style)),
+                }
+            }
+        }
+    }
+    TextWithStyle::new(text, highlights)
+}
+
+/// State of the picker shown above the list.
 #[derive(Debug, Clone)]
-pub struct Picker(TextWithStyle);
+pub struct PickerStatus {
+    pub query: SharedString,
+    pub cursor: u32,
+    pub matched_count: u32,
+    pub item_count: u32,
+    pub show_preview: bool,
+}
+
+/// Native replacement for `helix_term::ui::Picker`, rendered by the overlay view.
+pub struct Picker {
+    source: Box<dyn Source>,
+    query: String,
+    previous_query: String,
+    cursor: u32,
+    show_preview: bool,
+    query_fn: Option<QueryFn>,
+}
 
-// TODO: this is copy-paste from Prompt, refactor it later
 impl Picker {
-    pub fn make<T: helix_term::ui::menu::Item>(
-        editor: &mut helix_view::Editor,
-        prompt: &mut helix_term::ui::Picker<T>,
-    ) -> Self {
-        use helix_term::compositor::Component;
-        let area = editor.tree.area();
-        let compositor_rect = helix_view::graphics::Rect {
-            x: 0,
-            y: 0,
-            width: area.width * 2 / 3,
-            height: area.height,
+    pub const ID: &'static str = "native-picker";
+
+    pub fn new<T: Item>(
+        options: Vec<T>,
+        editor_data: T::Data,
+        callback_fn: impl Fn(&mut commands::Context, &T, Action) + 'static,
+        file_fn: Option<FileCallback<T>>,
+    ) -> (Self, Injector<T>) {
+        let nucleo = Nucleo::new(
+            Config::DEFAULT,
+            Arc::new(helix_event::request_redraw),
+            None,
+            1,
+        );
+        let injector = Injector {
+            dst: nucleo.injector(),
+            editor_data: Arc::new(editor_data),
+            shutdown: Arc::new(AtomicBool::new(false)),
         };
+        for item in options {
+            inject_item(&injector.dst, item, &injector.editor_data);
+        }
 
-        let mut comp_ctx = helix_term::compositor::Context {
-            editor,
-            scroll: None,
-            jobs: &mut helix_term::job::Jobs::new(),
+        let matcher = Matcher {
+            nucleo,
+            editor_data: injector.editor_data.clone(),
+            shutdown: injector.shutdown.clone(),
+            callback_fn: Box::new(callback_fn),
+            file_fn,
+        };
+        let show_preview = matcher.file_fn.is_some();
+        let picker = Self {
+            source: Box::new(matcher),
+            query: String::new(),
+            previous_query: String::new(),
+            cursor: 0,
+            show_preview,
+            query_fn: None,
         };
-        let mut buf = tui::buffer::Buffer::empty(compositor_rect);
-        prompt.render(compositor_rect, &mut buf, &mut comp_ctx);
-        Self(TextWithStyle::from_buffer(buf))
-    }
-}
-
-#[derive(IntoElement)]
-pub struct PickerElement {
-    pub picker: Picker,
-    pub focus: FocusHandle,
-}
-
-impl RenderOnce for PickerElement {
-    fn render(self, cx: &mut WindowContext) -> impl IntoElement {
-        let bg_color = self
-            .picker
-            .0
-            .style(0)
-            .and_then(|style| style.background_color);
-        let mut default_style = TextStyle::default();
-        default_style.font_family = "JetBrains Mono".into();
-        default_style.font_size = px(12.).into();
-        default_style.background_color = bg_color;
-
-        // println!("picker: {:?}", self.picker.0);
-        let text = self.picker.0.into_styled_text(&default_style);
-        cx.focus(&self.focus);
-        div()
-            .track_focus(&self.focus)
-            .flex()
-            .flex_col()
-            .bg(bg_color.unwrap_or(black()))
-            .shadow_sm()
-            .rounded_sm()
-            .text_color(hsla(1., 1., 1., 1.))
-            .font(cx.global::<crate::FontSettings>().fixed_font.clone())
-            .text_size(px(12.))
-            .line_height(px(1.3) * px(12.))
-            .child(text)
+        (picker, injector)
+    }
+
+    /// Asks `query_fn` for new items whenever the query changes, replacing the
+    /// current ones. They are still matched against the query afterwards.
+    pub fn with_query_fn<T: Item>(mut self, query_fn: QueryCallback<T>) -> Self {
+        self.query_fn = Some(Box::new(move |query, editor| {
+            let Some(future) = query_fn(query, editor) else {
+                return;
+            };
+            let query = query.to_string();
+            tokio::spawn(async move {
+                match future.await {
+                    Ok(items) => {
+                        dispatch(move |_editor, compositor| {
+                            if let Some(picker) = compositor.find_id::<Picker>(Picker::ID) {
+                                picker.replace_items(&query, items);
+                            }
+                        })
+                        .await
+                    }
+                    Err(err) => log::error!("picker query failed: {err}"),
+                }
+            });
+        }));
+        self
+    }
+
+    // results of a query that has changed since are dropped
+    fn replace_items<T: Item>(&mut self, query: &str, items: Vec<T>) {
+        if self.query != query {
+            return;
+        }
+        if let Some(matcher) = self.source.as_any_mut().downcast_mut::<Matcher<T>>() {
+            matcher.replace_items(items);
+            self.cursor = 0;
+        }
     }
+
+    /// Lets the matcher pick up new items and results from its worker threads.
+    pub fn tick(&mut self) {
+        self.source.tick();
+        self.cursor = self
+            .cursor
+            .min(self.source.matched_count().saturating_sub(1));
+    }
+
+    pub fn status(&self) -> PickerStatus {
+        PickerStatus {
+            query: self.query.clone().into(),
+            cursor: self.cursor,
+            matched_count: self.source.matched_count(),
+            item_count: self.source.item_count(),
+            show_preview: self.show_preview,
+        }
+    }
+
+    /// Formatted matches in `range`, the list only asks for the visible ones.
+    pub fn rows(&self, range: Range<u32>, editor: &Editor) -> Vec<TextWithStyle> {
+        let highlight = editor.theme.get("special").add_modifier(Modifier::BOLD);
+        range
+            .filter_map(|idx| self.source.row(idx, highlight))
+            .collect()
+    }
+
+    pub fn set_cursor(&mut self, idx: u32) {
+        self.cursor = idx.min(self.source.matched_count().saturating_sub(1));
+    }
+
+    fn move_by(&mut self, amount: u32, direction: Direction) {
+        let len = self.source.matched_count();
+        if len == 0 {
+            return;
+        }
+        let (cursor, amount, len) = (i64::from(self.cursor), i64::from(amount), i64::from(len));
+        let cursor = match direction {
+            Direction::Forward => cursor + amount,
+            Direction::Backward => cursor - amount,
+        };
+        self.cursor = cursor.rem_euclid(len) as u32;
+    }
+
+    fn update_query(&mut self, editor: &mut Editor) {
+        let append = self.query.starts_with(&self.previous_query);
+        self.source.reparse(&self.query, append);
+        self.previous_query.clone_from(&self.query);
+        self.cursor = 0;
+        if let Some(query_fn) = &self.query_fn {
+            query_fn(&self.query, editor);
+        }
+    }
+
+    fn delete_word_backward(&mut self) {
+        let trimmed = self.query.trim_end();
+        let end = trimmed
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(idx, c)| idx + c.len_utf8());
+        self.query.truncate(end);
+    }
+
+    // runs the item's callback like a command, closing the picker before
+    // the layers the callback pushes are shown
+    fn accept(&self, cx: &mut compositor::Context, action: Action) -> compositor::Callback {
+        let mut ctx = commands::Context {
+            register: None,
+            count: None,
+            editor: &mut *cx.editor,
+            callback: Vec::new(),
+            on_next_key_callback: None,
+            jobs: &mut *cx.jobs,
+        };
+        if self.source.matched_count() > 0 {
+            self.source.accept(self.cursor, &mut ctx, action);
+        }
+        let callbacks = ctx.callback;
+        Box::new(move |compositor, cx| {
+            Self::close(compositor);
+            for callback in callbacks {
+                callback(compositor, cx);
+            }
+        })
+    }
+
+    // keeps the picker around for `last_picker`
+    fn close(compositor: &mut compositor::Compositor) {
+        if let Some(picker) = compositor.remove(Self::ID) {
+            LAST_PICKER.with(|last| *last.borrow_mut() = Some(picker));
+        }
+    }
+
+    /// Shows the selected item in `previews`, files are loaded in the background.
+    pub fn update_preview(&self, editor: &Editor, previews: &mut Previews) {
+        let location = if self.show_preview && self.source.matched_count() > 0 {
+            self.source.location(self.cursor, editor)
+        } else {
+            None
+        };
+        match location {
+            Some(location) => previews.show(editor, location),
+            None => previews.shown = None,
+        }
+    }
+}
+
+impl Component for Picker {
+    fn handle_event(&mut self, event: &Event, cx: &mut compositor::Context) -> EventResult {
+        let Event::Key(key_event) = event else {
+            return EventResult::Ignored(None);
+        };
+
+        let close_fn: compositor::Callback = Box::new(|compositor, _| Self::close(compositor));
+
+        match *key_event {
+            key!(Esc) | ctrl!('c') => return EventResult::Consumed(Some(close_fn)),
+            shift!(Tab) | key!(Up) | ctrl!('p') => self.move_by(1, Direction::Backward),
+            key!(Tab) | key!(Down) | ctrl!('n') => self.move_by(1, Direction::Forward),
+            key!(PageUp) | ctrl!('u') => self.move_by(PAGE_SIZE, Direction::Backward),
+            key!(PageDown) | ctrl!('d') => self.move_by(PAGE_SIZE, Direction::Forward),
+            key!(Home) => self.cursor = 0,
+            key!(End) => self.cursor = self.source.matched_count().saturating_sub(1),
+            key!(Enter) => return EventResult::Consumed(Some(self.accept(cx, Action::Replace))),
+            ctrl!('s') => {
+                return EventResult::Consumed(Some(self.accept(cx, Action::HorizontalSplit)))
+            }
+            ctrl!('v') => {
+                return EventResult::Consumed(Some(self.accept(cx, Action::VerticalSplit)))
+            }
+            ctrl!('t') => self.show_preview = !self.show_preview,
+            key!(Backspace) => {
+                self.query.pop();
+                self.update_query(cx.editor);
+            }
+            ctrl!('w') => {
+                self.delete_word_backward();
+                self.update_query(cx.editor);
+            }
+            KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers,
+            } if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                self.query.push(c);
+                self.update_query(cx.editor);
+            }
+            _ => {}
+        }
+        EventResult::Consumed(None)
+    }
+
+    fn render(
+        &mut self,
+        _area: Rect,
+        _frame: &mut tui::buffer::Buffer,
+        _ctx: &mut compositor::Context,
+    ) {
+        // rendered by the overlay view
+    }
+
+    fn id(&self) -> Option<&'static str> {
+        Some(Self::ID)
+    }
+}
+
+// contents of a file read for the preview, with its encoding and BOM
+type PreviewText = (Rope, &'static Encoding, bool);
+
+enum PreviewFile {
+    Loading,
+    Loaded(Preview),
+    // missing, binary or too large
+    Unavailable,
+}
+
+/// Files opened for the preview pane of the picker. They are read on a
+/// background thread and kept until the picker closes.
+pub struct Previews {
+    files: HashMap<PathBuf, PreviewFile>,
+    shown: Option<PathBuf>,
+    loaded_tx: mpsc::Sender<(PathBuf, Option<PreviewText>)>,
+    loaded_rx: mpsc::Receiver<(PathBuf, Option<PreviewText>)>,
+}
+
+impl Default for Previews {
+    fn default() -> Self {
+        let (loaded_tx, loaded_rx) = mpsc::channel();
+        Self {
+            files: HashMap::new(),
+            shown: None,
+            loaded_tx,
+            loaded_rx,
+        }
+    }
+}
+
+impl Previews {
+    pub fn shown(&self) -> Option<&Preview> {
+        match self.files.get(self.shown.as_ref()?)? {
+            PreviewFile::Loaded(preview) => Some(preview),
+            _ => None,
+        }
+    }
+
+    pub fn shown_mut(&mut self) -> Option<&mut Preview> {
+        match self.files.get_mut(self.shown.as_ref()?)? {
+            PreviewFile::Loaded(preview) => Some(preview),
+            _ => None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+        self.shown = None;
+    }
+
+    fn show(&mut self, editor: &Editor, (path, range): FileLocation) {
+        while let Ok((path, text)) = self.loaded_rx.try_recv() {
+            let file = match text {
+                Some(text) => PreviewFile::Loaded(Preview::new(editor, path.clone(), text)),
+                None => PreviewFile::Unavailable,
+            };
+            self.files.insert(path, file);
+        }
+
+        match self.files.entry(path.clone()) {
+            Entry::Occupied(mut entry) => {
+                if let PreviewFile::Loaded(preview) = entry.get_mut() {
+                    preview.set_range(range);
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(PreviewFile::Loading);
+                let loaded_tx = self.loaded_tx.clone();
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let text = read_preview(&path);
+                    if loaded_tx.send((path, text)).is_ok() {
+                        helix_event::request_redraw();
+                    }
+                });
+            }
+        }
+        self.shown = Some(path);
+    }
+}
+
+fn read_preview(path: &Path) -> Option<PreviewText> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_FILE_SIZE_FOR_PREVIEW {
+        return None;
+    }
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut buf = [0; 1024];
+    let n = file.read(&mut buf).ok()?;
+    if content_inspector::inspect(&buf[..n]).is_binary() {
+        return None;
+    }
+    let mut reader = (&buf[..n]).chain(file);
+    helix_view::document::from_reader(&mut reader, None).ok()
+}
+
+/// A file opened for the preview pane, outside of the editor's documents.
+pub struct Preview {
+    range: Option<(usize, usize)>,
+    pub doc: Document,
+    pub view: View,
+}
+
+impl Preview {
+    fn new(editor: &Editor, path: PathBuf, (text, encoding, has_bom): PreviewText) -> Self {
+        let mut doc = Document::from(text, Some((encoding, has_bom)), editor.config.clone());
+        doc.set_path(Some(&path));
+        doc.detect_language(editor.syn_loader.clone());
+        let view = View::new(doc.id(), editor.config().gutters.clone());
+        Self {
+            range: None,
+            doc,
+            view,
+        }
+    }
+
+    fn set_range(&mut self, range: Option<(usize, usize)>) {
+        if self.range == range && self.doc.selections().contains_key(&self.view.id) {
+            return;
+        }
+        self.range = range;
+        let text = self.doc.text();
+        let line = range
+            .map_or(0, |(start, _)| start)
+            .min(text.len_lines().saturating_sub(1));
+        let pos = text.line_to_char(line);
+        self.doc.set_selection(self.view.id, Selection::point(pos));
+        align_view(&self.doc, &mut self.view, Align::Center);
+    }
+
+    /// Sizes the view to the preview pane, keeping the previewed line centered.
+    pub fn resize(&mut self, area: Rect) {
+        if self.view.area != area {
+            self.view.area = area;
+            align_view(&self.doc, &mut self.view, Align::Center);
+        }
+    }
+}
+
+// mostly copy pasted from helix_term/src/ui/mod.rs
+pub fn file_picker(root: PathBuf, config: &helix_view::editor::Config) -> Picker {
+    use ignore::{types::TypesBuilder, WalkBuilder};
+    use std::time::Instant;
+
+    let now = Instant::now();
+
+    let dedup_symlinks = config.file_picker.deduplicate_links;
+    let absolute_root = root.canonicalize().unwrap_or_else(|_| root.clone());
+
+    let mut walk_builder = WalkBuilder::new(&root);
+    walk_builder
+        .hidden(config.file_picker.hidden)
+        .parents(config.file_picker.parents)
+        .ignore(config.file_picker.ignore)
+        .follow_links(config.file_picker.follow_symlinks)
+        .git_ignore(config.file_picker.git_ignore)
+        .git_global(config.file_picker.git_global)
+        .git_exclude(config.file_picker.git_exclude)
+        .sort_by_file_name(|name1, name2| name1.cmp(name2))
+        .max_depth(config.file_picker.max_depth)
+        .filter_entry(move |entry| filter_picker_entry(entry, &absolute_root, dedup_symlinks));
+
+    walk_builder.add_custom_ignore_filename(helix_loader::config_dir().join("ignore"));
+    walk_builder.add_custom_ignore_filename(".helix/ignore");
+
+    // We want to exclude files that the editor can't handle yet
+    let mut type_builder = TypesBuilder::new();
+    type_builder
+        .add(
+            "compressed",
+            "*.{zip,gz,bz2,zst,lzo,sz,tgz,tbz2,lz,lz4,lzma,lzo,z,Z,xz,7z,rar,cab}",
+        )
+        .expect("Invalid type definition");
+    type_builder.select("all");
+    let excluded_types = type_builder
+        .build()
+        .expect("failed to build excluded_types");
+    walk_builder.types(excluded_types);
+    let mut files = walk_builder.build().filter_map(|entry| {
+        let entry = entry.ok()?;
+        if !entry.file_type()?.is_file() {
+            return None;
+        }
+        Some(entry.into_path())
+    });
+    log::debug!("file_picker init {:?}", Instant::now().duration_since(now));
+
+    let (picker, injector) = Picker::new(
+        Vec::new(),
+        root,
+        move |cx, path: &PathBuf, action| {
+            if let Err(err) = cx.editor.open(path, action) {
+                cx.editor
+                    .set_error(format!("unable to open \"{}\": {}", path.display(), err));
+            }
+        },
+        Some(Box::new(|_editor, path: &PathBuf| {
+            Some((path.clone(), None))
+        })),
+    );
+    let timeout = std::time::Instant::now() + std::time::Duration::from_millis(30);
+
+    let mut hit_timeout = false;
+    for file in &mut files {
+        if injector.push(file).is_err() {
+            break;
+        }
+        if std::time::Instant::now() >= timeout {
+            hit_timeout = true;
+            break;
+        }
+    }
+    if hit_timeout {
+        std::thread::spawn(move || {
+            for file in files {
+                if injector.push(file).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    picker
+}
+
+pub fn filter_picker_entry(entry: &ignore::DirEntry, root: &Path, dedup_symlinks: bool) -> bool {
+    // We always want to ignore the .git directory, otherwise if
+    // `ignore` is turned off, we end up with a lot of noise
+    // in our picker.
+    if entry.file_name() == ".git" {
+        return false;
+    }
+
+    // We also ignore symlinks that point inside the current directory
+    // if `dedup_links` is enabled.
+    if dedup_symlinks && entry.path_is_symlink() {
+        return entry
+            .path()
+            .canonicalize()
+            .ok()
+            .map_or(false, |path| !path.starts_with(root));
+    }
+
+    true
 }
@@ -0,0 +1,451 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use gpui::prelude::FluentBuilder;
+use gpui::*;
+use helix_core::syntax::LanguageServerFeature;
+use helix_event::{
+    cancelable_future, cancelation, register_hook, send_blocking, AsyncHook, CancelRx, CancelTx,
+};
+use helix_lsp::lsp;
+use helix_stdx::rope::RopeSliceExt;
+use helix_term::compositor::{self, Component, Compositor, EventResult};
+use helix_term::config::Config;
+use helix_term::events::{OnModeSwitch, PostInsertChar};
+use helix_term::job::{dispatch, dispatch_blocking};
+use helix_view::document::Mode;
+use helix_view::events::{DocumentDidChange, SelectionDidChange};
+use helix_view::handlers::lsp::{SignatureHelpEvent, SignatureHelpInvoked};
+use helix_view::handlers::Handlers;
+use helix_view::input::Event;
+use helix_view::{alt, current, Editor, ViewId};
+use tokio::sync::mpsc::Sender;
+use tokio::time::Instant;
+
+use crate::utils::color_to_hsla;
+
+#[derive(Debug, Default)]
+enum State {
+    Open,
+    #[default]
+    Closed,
+    Pending {
+        request: CancelTx,
+    },
+}
+
+/// debounce timeout in ms, value taken from VSCode
+const TIMEOUT: u64 = 120;
+
+// mostly copy pasted from helix_term/src/handlers/signature_help.rs
+#[derive(Default)]
+pub struct SignatureHelpHandler {
+    trigger: Option<SignatureHelpInvoked>,
+    state: State,
+}
+
+impl AsyncHook for SignatureHelpHandler {
+    type Event = SignatureHelpEvent;
+
+    fn handle_event(&mut self, event: Self::Event, timeout: Option<Instant>) -> Option<Instant> {
+        match event {
+            SignatureHelpEvent::Invoked => {
+                self.trigger = Some(SignatureHelpInvoked::Manual);
+                self.state = State::Closed;
+                self.finish_debounce();
+                return None;
+            }
+            SignatureHelpEvent::Trigger => {}
+            SignatureHelpEvent::ReTrigger => {
+                // don't retrigger if we aren't open/pending yet
+                if matches!(self.state, State::Closed) {
+                    return timeout;
+                }
+            }
+            SignatureHelpEvent::Cancel => {
+                self.state = State::Closed;
+                return None;
+            }
+            SignatureHelpEvent::RequestComplete { open } => {
+                // don't cancel rerequest that was already triggered
+                if let State::Pending { request } = &self.state {
+                    if !request.is_closed() {
+                        return timeout;
+                    }
+                }
+                self.state = if open { State::Open } else { State::Closed };
+                return timeout;
+            }
+        }
+        if self.trigger.is_none() {
+            self.trigger = Some(SignatureHelpInvoked::Automatic)
+        }
+        Some(Instant::now() + Duration::from_millis(TIMEOUT))
+    }
+
+    fn finish_debounce(&mut self) {
+        let invocation = self.trigger.take().unwrap();
+        let (tx, rx) = cancelation();
+        self.state = State::Pending { request: tx };
+        dispatch_blocking(move |editor, _| request_signature_help(editor, invocation, rx))
+    }
+}
+
+fn request_signature_help(editor: &mut Editor, invoked: SignatureHelpInvoked, cancel: CancelRx) {
+    let (view, doc) = current!(editor);
+
+    // TODO merge multiple language server signature help into one instead of
+    // just taking the first language server that supports it
+    let future = doc
+        .language_servers_with_feature(LanguageServerFeature::SignatureHelp)
+        .find_map(|language_server| {
+            let pos = doc.position(view.id, language_server.offset_encoding());
+            language_server.text_document_signature_help(doc.identifier(), pos, None)
+        });
+
+    let Some(future) = future else {
+        // Do not show the message if signature help was invoked
+        // automatically on backspace, trigger characters, etc.
+        if invoked == SignatureHelpInvoked::Manual {
+            editor.set_error("No configured language server supports signature-help");
+        }
+        return;
+    };
+
+    tokio::spawn(async move {
+        match cancelable_future(future, cancel).await {
+            Some(Ok(json)) => {
+                let response = match serde_json::from_value::<Option<lsp::SignatureHelp>>(json) {
+                    Ok(response) => response,
+                    Err(err) => {
+                        log::error!("invalid signature help response: {err}");
+                        return;
+                    }
+                };
+                dispatch(move |editor, compositor| {
+                    show_signature_help(editor, compositor, invoked, response)
+                })
+                .await
+            }
+            Some(Err(err)) => log::error!("signature help request failed: {err}"),
+            None => (),
+        }
+    });
+}
+
+fn show_signature_help(
+    editor: &mut Editor,
+    compositor: &mut Compositor,
+    invoked: SignatureHelpInvoked,
+    response: Option<lsp::SignatureHelp>,
+) {
+    let config = &editor.config();
+
+    if !(config.lsp.auto_signature_help || invoked == SignatureHelpInvoked::Manual) {
+        return;
+    }
+
+    // If the signature help invocation is automatic, don't show it outside of Insert Mode:
+    // the server was probably slow to respond and the user has already moved on
+    if invoked == SignatureHelpInvoked::Automatic && editor.mode != Mode::Insert {
+        return;
+    }
+
+    let response = match response {
+        // According to the spec the response should be None if there
+        // are no signatures, but some servers don't follow this.
+        Some(s) if !s.signatures.is_empty() => s,
+        _ => {
+            send_blocking(
+                &editor.handlers.signature_hints,
+                SignatureHelpEvent::RequestComplete { open: false },
+            );
+            compositor.remove(SignatureHelp::ID);
+            return;
+        }
+    };
+    send_blocking(
+        &editor.handlers.signature_hints,
+        SignatureHelpEvent::RequestComplete { open: true },
+    );
+
+    let signatures: Vec<Signature> = response
+        .signatures
+        .into_iter()
+        .map(|s| {
+            let active_param_range = || -> Option<(usize, usize)> {
+                let param_idx = s
+                    .active_parameter
+                    .or(response.active_parameter)
+                    .unwrap_or(0) as usize;
+                let param = s.parameters.as_ref()?.get(param_idx)?;
+                match &param.label {
+                    lsp::ParameterLabel::Simple(string) => {
+                        let start = s.label.find(string.as_str())?;
+                        Some((start, start + string.len()))
+                    }
+                    lsp::ParameterLabel::LabelOffsets([start, end]) => {
+                        // LS sends offsets based on utf-16 based string representation
+                        // but highlighting is done using byte offset.
+                        use helix_core::str_utils::char_to_byte_idx;
+                        let from = char_to_byte_idx(&s.label, *start as usize);
+                        let to = char_to_byte_idx(&s.label, *end as usize);
+                        Some((from, to))
+                    }
+                }
+            };
+            Signature {
+                active_param_range: active_param_range(),
+                signature: s.label,
+                signature_doc: s.documentation.map(|doc| match doc {
+                    lsp::Documentation::String(s) => s,
+                    lsp::Documentation::MarkupContent(markup) => markup.value,
+                }),
+            }
+        })
+        .collect();
+
+    let old_active = compositor
+        .find::<SignatureHelp>()
+        .map(|help| help.active_signature);
+    let active_signature = old_active
+        .unwrap_or_else(|| response.active_signature.unwrap_or_default() as usize)
+        .min(signatures.len() - 1);
+
+    let view_id = editor.tree.focus;
+    compositor.remove(SignatureHelp::ID);
+    compositor.push(Box::new(SignatureHelp {
+        signatures,
+        active_signature,
+        view_id,
+    }));
+}
+
+fn signature_help_post_insert_char_hook(
+    tx: &Sender<SignatureHelpEvent>,
+    PostInsertChar { cx, .. }: &mut PostInsertChar<'_, '_>,
+) -> anyhow::Result<()> {
+    if !cx.editor.config().lsp.auto_signature_help {
+        return Ok(());
+    }
+    let (view, doc) = current!(cx.editor);
+    // TODO support multiple language servers (not just the first that is found)
+    let Some(language_server) = doc
+        .language_servers_with_feature(LanguageServerFeature::SignatureHelp)
+        .next()
+    else {
+        return Ok(());
+    };
+
+    let capabilities = language_server.capabilities();
+
+    if let lsp::ServerCapabilities {
+        signature_help_provider:
+            Some(lsp::SignatureHelpOptions {
+                trigger_characters: Some(triggers),
+                // TODO: retrigger_characters
+                ..
+            }),
+        ..
+    } = capabilities
+    {
+        let mut text = doc.text().slice(..);
+        let cursor = doc.selection(view.id).primary().cursor(text);
+        text = text.slice(..cursor);
+        if triggers.iter().any(|trigger| text.ends_with(trigger)) {
+            send_blocking(tx, SignatureHelpEvent::Trigger)
+        }
+    }
+    Ok(())
+}
+
+pub fn register_hooks(handlers: &Handlers, config: Arc<ArcSwap<Config>>) {
+    let tx = handlers.signature_hints.clone();
+    register_hook!(move |event: &mut OnModeSwitch<'_, '_>| {
+        match (event.old_mode, event.new_mode) {
+            (Mode::Insert, _) => {
+                send_blocking(&tx, SignatureHelpEvent::Cancel);
+                event.cx.callback.push(Box::new(|compositor, _| {
+                    compositor.remove(SignatureHelp::ID);
+                }));
+            }
+            (_, Mode::Insert) => {
+                if event.cx.editor.config().lsp.auto_signature_help {
+                    send_blocking(&tx, SignatureHelpEvent::Trigger);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    });
+
+    let tx = handlers.signature_hints.clone();
+    register_hook!(
+        move |event: &mut PostInsertChar<'_, '_>| signature_help_post_insert_char_hook(&tx, event)
+    );
+
+    let tx = handlers.signature_hints.clone();
+    let config_1 = config.clone();
+    register_hook!(move |_event: &mut DocumentDidChange<'_>| {
+        if config_1.load().editor.lsp.auto_signature_help {
+            send_blocking(&tx, SignatureHelpEvent::ReTrigger);
+        }
+        Ok(())
+    });
+
+    let tx = handlers.signature_hints.clone();
+    register_hook!(move |_event: &mut SelectionDidChange<'_>| {
+        if config.load().editor.lsp.auto_signature_help {
+            send_blocking(&tx, SignatureHelpEvent::ReTrigger);
+        }
+        Ok(())
+    });
+}
+
+#[derive(Debug, Clone)]
+struct Signature {
+    signature: String,
+    signature_doc: Option<String>,
+    /// Part of signature text
+    active_param_range: Option<(usize, usize)>,
+}
+
+/// Signature help popup, lives in the compositor so that it can cycle overloads
+/// on key presses. It is rendered natively by the document view.
+pub struct SignatureHelp {
+    signatures: Vec<Signature>,
+    active_signature: usize,
+    view_id: ViewId,
+}
+
+impl SignatureHelp {
+    pub const ID: &'static str = "signature-help";
+
+    /// Snapshot of the popup for rendering.
+    pub fn popup(&self, editor: &Editor) -> SignatureHelpPopup {
+        let theme = &editor.theme;
+        let text_style = theme.get("ui.text");
+        let popup_style = theme.get("ui.popup");
+        let active_param_style = theme.get("ui.selection");
+        let counter_style = theme.get("ui.text.inactive");
+
+        let fg = text_style.fg.and_then(color_to_hsla).unwrap_or(white());
+        let signature = &self.signatures[self.active_signature];
+        let counter = (self.signatures.len() > 1)
+            .then(|| format!("({}/{})", self.active_signature + 1, self.signatures.len()));
+
+        SignatureHelpPopup {
+            view_id: self.view_id,
+            signature: signature.signature.clone().into(),
+            active_param_range: signature.active_param_range.map(|(start, end)| start..end),
+            signature_doc: signature.signature_doc.clone(),
+            counter,
+            fg,
+            bg: popup_style.bg.and_then(color_to_hsla).unwrap_or(black()),
+            active_param_fg: active_param_style.fg.and_then(color_to_hsla),
+            active_param_bg: active_param_style.bg.and_then(color_to_hsla),
+            counter_fg: counter_style.fg.and_then(color_to_hsla).unwrap_or(fg),
+        }
+    }
+}
+
+impl Component for SignatureHelp {
+    fn handle_event(&mut self, event: &Event, _cx: &mut compositor::Context) -> EventResult {
+        let Event::Key(event) = event else {
+            return EventResult::Ignored(None);
+        };
+
+        if self.signatures.len() <= 1 {
+            return EventResult::Ignored(None);
+        }
+
+        match *event {
+            alt!('p') => {
+                self.active_signature = self
+                    .active_signature
+                    .checked_sub(1)
+                    .unwrap_or(self.signatures.len() - 1);
+                EventResult::Consumed(None)
+            }
+            alt!('n') => {
+                self.active_signature = (self.active_signature + 1) % self.signatures.len();
+                EventResult::Consumed(None)
+            }
+            _ => EventResult::Ignored(None),
+        }
+    }
+
+    fn render(
+        &mut self,
+        _area: helix_view::graphics::Rect,
+        _frame: &mut tui::buffer::Buffer,
+        _ctx: &mut compositor::Context,
+    ) {
+        // rendered by the document view
+    }
+
+    fn id(&self) -> Option<&'static str> {
+        Some(Self::ID)
+    }
+}
+
+#[derive(Debug, Clone, IntoElement)]
+pub struct SignatureHelpPopup {
+    pub view_id: ViewId,
+    signature: SharedString,
+    active_param_range: Option<std::ops::Range<usize>>,
+    signature_doc: Option<String>,
+    counter: Option<String>,
+    fg: Hsla,
+    bg: Hsla,
+    active_param_fg: Option<Hsla>,
+    active_param_bg: Option<Hsla>,
+    counter_fg: Hsla,
+}
+
+impl RenderOnce for SignatureHelpPopup {
+    fn render(self, cx: &mut WindowContext) -> impl IntoElement {
+        let font = cx.global::<crate::FontSettings>().fixed_font.clone();
+        let mut default_style = cx.global::<crate::FontSettings>().ui_text_style();
+        default_style.color = self.fg;
+
+        let highlights = self
+            .active_param_range
+            .map(|range| {
+                let style = HighlightStyle {
+                    color: self.active_param_fg,
+                    background_color: self.active_param_bg,
+                    ..Default::default()
+                };
+                vec![(range, style)]
+            })
+            .unwrap_or_default();
+        let signature = StyledText::new(self.signature).with_highlights(&default_style, highlights);
+
+        div()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .p_2()
+            .shadow_sm()
+            .rounded_sm()
+            .font(font)
+            .text_size(default_style.font_size)
+            .line_height(default_style.line_height)
+            .text_color(self.fg)
+            .bg(self.bg)
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .gap_2()
+                    .whitespace_nowrap()
+                    .child(signature)
+                    .when_some(self.counter, |this, counter| {
+                        this.child(div().text_color(self.counter_fg).child(counter))
+                    }),
+            )
+            .when_some(self.signature_doc, |this, doc| this.child(doc))
+    }
+}
@@ -1,4 +1,4 @@
-use crate::utils::color_to_hsla;
+use crate::utils::{color_to_hsla, style_colors, style_to_highlight};
 use crate::Core;
 use gpui::*;
 use helix_view::{DocumentId, ViewId};
@@ -74,11 +74,12 @@ impl StatusLine {
             for span in spans {
                 let len = span.content.len();
                 text.push_str(&span.content);
-                let fg = span.style.fg.and_then(color_to_hsla).unwrap_or(base_fg);
-                let bg = span.style.bg.and_then(color_to_hsla).unwrap_or(base_bg);
-                let mut run = HighlightStyle::default();
-                run.color = Some(fg);
-                run.background_color = Some(bg);
+                let (fg, bg) = style_colors(span.style, Some(base_fg), Some(base_bg));
+                let run = HighlightStyle {
+                    color: Some(fg.unwrap_or(base_fg)),
+                    background_color: Some(bg.unwrap_or(base_bg)),
+                    ..style_to_highlight(span.style)
+                };
                 runs.push(((idx..idx + len), run));
                 idx += len;
             }
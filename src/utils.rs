@@ -1,28 +1,225 @@
-use gpui::{rgb, HighlightStyle, Hsla, Keystroke, SharedString, StyledText, TextStyle};
+use gpui::{
+    px, rgb, transparent_black, Font, FontStyle, FontWeight, HighlightStyle, Hsla, Keystroke,
+    Pixels, SharedString, StrikethroughStyle, StyledText, TextRun, TextStyle, UnderlineStyle,
+    WindowContext,
+};
+use once_cell::sync::OnceCell;
 use tui::buffer::Buffer;
 
+/// Colours of the 256 colour palette used by themes made for terminals.
+static PALETTE: OnceCell<[Hsla; 256]> = OnceCell::new();
+
+/// Sets up the palette, `colors` are `#rrggbb` strings replacing
+/// xterm's default colours from the first one on.
+pub fn init_palette(colors: &[String]) {
+    let mut palette = xterm_palette();
+    for (idx, color) in colors.iter().take(palette.len()).enumerate() {
+        match parse_hex_color(color) {
+            Some(color) => palette[idx] = color,
+            None => log::warn!("invalid palette color {:?}, expected #rrggbb", color),
+        }
+    }
+    let _ = PALETTE.set(palette);
+}
+
+fn parse_hex_color(color: &str) -> Option<Hsla> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(rgb(value).into())
+}
+
+fn xterm_palette() -> [Hsla; 256] {
+    const ANSI: [u32; 16] = [
+        0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5, 0x7f7f7f,
+        0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff, 0x00ffff, 0xffffff,
+    ];
+    // levels of the 6x6x6 colour cube
+    let level = |value: usize| {
+        if value == 0 {
+            0
+        } else {
+            55 + 40 * value as u32
+        }
+    };
+
+    let mut palette = [gpui::black(); 256];
+    for (idx, color) in palette.iter_mut().enumerate() {
+        let value = match idx {
+            0..=15 => ANSI[idx],
+            16..=231 => {
+                let idx = idx - 16;
+                level(idx / 36) << 16 | level(idx / 6 % 6) << 8 | level(idx % 6)
+            }
+            // grayscale ramp
+            _ => {
+                let gray = 8 + 10 * (idx - 232) as u32;
+                gray << 16 | gray << 8 | gray
+            }
+        };
+        *color = rgb(value).into();
+    }
+    palette
+}
+
 pub fn color_to_hsla(color: helix_view::graphics::Color) -> Option<Hsla> {
-    use gpui::{black, blue, green, red, white, yellow};
     use helix_view::graphics::Color;
-    match color {
-        Color::White => Some(white()),
-        Color::Black => Some(black()),
-        Color::Blue => Some(blue()),
-        Color::Green => Some(green()),
-        Color::Red => Some(red()),
-        Color::Yellow => Some(yellow()),
+
+    // named colours are the terminal's first 16, in the order crossterm maps them to
+    let idx = match color {
+        Color::Reset => return None,
         Color::Rgb(r, g, b) => {
             let r = (r as u32) << 16;
             let g = (g as u32) << 8;
             let b = b as u32;
-            Some(rgb(r | g | b).into())
+            return Some(rgb(r | g | b).into());
         }
-        Color::Reset => None,
-        any => todo!("{:?} not implemented", any),
+        Color::Indexed(idx) => idx,
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::LightGray => 7,
+        Color::Gray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White => 15,
+    };
+    Some(PALETTE.get_or_init(xterm_palette)[idx as usize])
+}
+
+// gpui only draws straight and wavy underlines, dotted and dashed ones are drawn straight
+fn underline(style: helix_view::graphics::Style) -> Option<UnderlineStyle> {
+    use helix_view::graphics::UnderlineStyle as Underline;
+
+    let thickness = match style.underline_style? {
+        Underline::Reset => return None,
+        Underline::DoubleLine => px(2.),
+        Underline::Line | Underline::Curl | Underline::Dotted | Underline::Dashed => px(1.),
+    };
+    Some(UnderlineStyle {
+        thickness,
+        color: style.underline_color.and_then(color_to_hsla),
+        wavy: style.underline_style == Some(Underline::Curl),
+    })
+}
+
+fn strikethrough(style: helix_view::graphics::Style) -> Option<StrikethroughStyle> {
+    use helix_view::graphics::Modifier;
+
+    style
+        .add_modifier
+        .contains(Modifier::CROSSED_OUT)
+        .then_some(StrikethroughStyle {
+            thickness: px(1.),
+            color: None,
+        })
+}
+
+/// Foreground and background of a style with `REVERSED`, `DIM` and `HIDDEN` applied.
+/// Reversing swaps in `default_fg` and `default_bg` for colours the style doesn't set.
+pub fn style_colors(
+    style: helix_view::graphics::Style,
+    default_fg: Option<Hsla>,
+    default_bg: Option<Hsla>,
+) -> (Option<Hsla>, Option<Hsla>) {
+    use helix_view::graphics::Modifier;
+
+    let modifier = style.add_modifier;
+    let fg = style.fg.and_then(color_to_hsla);
+    let bg = style.bg.and_then(color_to_hsla);
+    let (mut fg, bg) = if modifier.contains(Modifier::REVERSED) {
+        (bg.or(default_bg), fg.or(default_fg))
+    } else {
+        (fg, bg)
+    };
+    if modifier.contains(Modifier::DIM) {
+        fg = fg.or(default_fg).map(|mut fg| {
+            fg.a *= 0.5;
+            fg
+        });
+    }
+    if modifier.contains(Modifier::HIDDEN) {
+        fg = Some(transparent_black());
     }
+    (fg, bg)
 }
 
-pub fn translate_key(ks: &Keystroke) -> helix_view::input::KeyEvent {
+pub fn style_to_highlight(style: helix_view::graphics::Style) -> HighlightStyle {
+    use helix_view::graphics::Modifier;
+
+    let (color, background_color) = style_colors(style, None, None);
+    // without a colour to dim, the inherited one is faded instead
+    let fade_out = (style.add_modifier.contains(Modifier::DIM) && color.is_none()).then_some(0.5);
+
+    HighlightStyle {
+        color,
+        background_color,
+        font_weight: style
+            .add_modifier
+            .contains(Modifier::BOLD)
+            .then_some(FontWeight::BOLD),
+        font_style: style
+            .add_modifier
+            .contains(Modifier::ITALIC)
+            .then_some(FontStyle::Italic),
+        underline: underline(style),
+        strikethrough: strikethrough(style),
+        fade_out,
+    }
+}
+
+/// Run of `len` bytes of text in `font` styled like `style`, colours it
+/// doesn't set are `fg` and no background unless it is reversed.
+pub fn text_run(
+    style: helix_view::graphics::Style,
+    len: usize,
+    font: &Font,
+    fg: Hsla,
+    bg: Hsla,
+) -> TextRun {
+    use helix_view::graphics::Modifier;
+
+    let (color, background_color) = style_colors(style, Some(fg), Some(bg));
+    let mut font = font.clone();
+    if style.add_modifier.contains(Modifier::BOLD) {
+        font.weight = FontWeight::BOLD;
+    }
+    if style.add_modifier.contains(Modifier::ITALIC) {
+        font.style = FontStyle::Italic;
+    }
+
+    TextRun {
+        len,
+        font,
+        color: color.unwrap_or(fg),
+        background_color,
+        underline: underline(style),
+        strikethrough: strikethrough(style),
+    }
+}
+
+/// Width of the cells documents are laid out on, the advance of `m`
+/// in `style` and the configured letter spacing.
+pub fn cell_width(style: &TextStyle, cx: &WindowContext) -> Pixels {
+    let font_id = cx.text_system().resolve_font(&style.font());
+    let font_size = style.font_size.to_pixels(cx.rem_size());
+    let advance = cx
+        .text_system()
+        .advance(font_id, font_size, 'm')
+        .unwrap()
+        .width;
+    advance + cx.global::<crate::FontSettings>().letter_spacing
+}
+
+/// Translates a gpui keystroke into a helix key event, `None` for keys helix doesn't know.
+pub fn translate_key(ks: &Keystroke) -> Option<helix_view::input::KeyEvent> {
     use helix_view::keyboard::{KeyCode, KeyModifiers};
 
     let mut modifiers = KeyModifiers::NONE;
@@ -35,29 +232,155 @@ pub fn translate_key(ks: &Keystroke) -> helix_view::input::KeyEvent {
     if ks.modifiers.shift {
         modifiers |= KeyModifiers::SHIFT;
     }
+    if ks.modifiers.platform {
+        modifiers |= KeyModifiers::SUPER;
+    }
+    // `function` is set by macOS for keys on the fn layer like the arrows,
+    // the key itself tells which one it was
+
     let key = ks.ime_key.as_ref().unwrap_or(&ks.key);
     let code = match key.as_str() {
         "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
         "enter" => KeyCode::Enter,
         "left" => KeyCode::Left,
         "right" => KeyCode::Right,
         "up" => KeyCode::Up,
         "down" => KeyCode::Down,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
         "tab" => KeyCode::Tab,
         "escape" => KeyCode::Esc,
         "space" => KeyCode::Char(' '),
-        /* TODO */
+        "menu" => KeyCode::Menu,
         any => {
-            let chars: Vec<char> = key.chars().collect();
-            if chars.len() == 1 {
-                KeyCode::Char(chars[0])
-            } else {
-                todo!("{:?} key not implemented yet", any)
+            let mut chars = any.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => match any.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n) if n > 0 => KeyCode::F(n),
+                    _ => {
+                        log::warn!("unknown key {:?}", ks);
+                        return None;
+                    }
+                },
             }
         }
     };
 
-    helix_view::input::KeyEvent { code, modifiers }
+    Some(helix_view::input::KeyEvent { code, modifiers })
+}
+
+/// Key at the same place as `key` on a US QWERTY keyboard, for chars
+/// of the Cyrillic and Greek layouts. Other keys are left as they are.
+pub fn physical_key(
+    key: helix_view::input::KeyEvent,
+    layout: crate::config::CyrillicLayout,
+) -> helix_view::input::KeyEvent {
+    use helix_view::keyboard::KeyCode;
+
+    let KeyCode::Char(c) = key.code else {
+        return key;
+    };
+    let mut lower = c.to_lowercase();
+    let (Some(lower), None) = (lower.next(), lower.next()) else {
+        return key;
+    };
+    let Some(qwerty) = qwerty_char(lower, layout) else {
+        return key;
+    };
+    let code = if lower == c {
+        qwerty
+    } else {
+        // shifted, the char the same key types with shift on QWERTY
+        match qwerty {
+            '[' => '{',
+            ']' => '}',
+            ';' => ':',
+            '\'' => '"',
+            ',' => '<',
+            '.' => '>',
+            '`' => '~',
+            c => c.to_ascii_uppercase(),
+        }
+    };
+    helix_view::input::KeyEvent {
+        code: KeyCode::Char(code),
+        ..key
+    }
+}
+
+// QWERTY char of the key typing the lower case `c` with the Russian, Ukrainian,
+// Belarusian or Greek layout
+fn qwerty_char(c: char, layout: crate::config::CyrillicLayout) -> Option<char> {
+    use crate::config::CyrillicLayout;
+
+    let qwerty = match c {
+        'і' if layout == CyrillicLayout::Belarusian => 'b',
+        'й' => 'q',
+        'ц' => 'w',
+        'у' => 'e',
+        'к' => 'r',
+        'е' => 't',
+        'н' => 'y',
+        'г' => 'u',
+        'ш' => 'i',
+        'щ' | 'ў' => 'o',
+        'з' => 'p',
+        'х' => '[',
+        'ъ' | 'ї' => ']',
+        'ф' => 'a',
+        'ы' | 'і' => 's',
+        'в' => 'd',
+        'а' => 'f',
+        'п' => 'g',
+        'р' => 'h',
+        'о' => 'j',
+        'л' => 'k',
+        'д' => 'l',
+        'ж' => ';',
+        'э' | 'є' => '\'',
+        'я' => 'z',
+        'ч' => 'x',
+        'с' => 'c',
+        'м' => 'v',
+        'и' => 'b',
+        'т' => 'n',
+        'ь' => 'm',
+        'б' => ',',
+        'ю' => '.',
+        'ё' | 'ґ' => '`',
+        'ς' => 'w',
+        'ε' => 'e',
+        'ρ' => 'r',
+        'τ' => 't',
+        'υ' => 'y',
+        'θ' => 'u',
+        'ι' => 'i',
+        'ο' => 'o',
+        'π' => 'p',
+        'α' => 'a',
+        'σ' => 's',
+        'δ' => 'd',
+        'φ' => 'f',
+        'γ' => 'g',
+        'η' => 'h',
+        'ξ' => 'j',
+        'κ' => 'k',
+        'λ' => 'l',
+        'ζ' => 'z',
+        'χ' => 'x',
+        'ψ' => 'c',
+        'ω' => 'v',
+        'β' => 'b',
+        'ν' => 'n',
+        'μ' => 'm',
+        _ => return None,
+    };
+    Some(qwerty)
 }
 
 /// Handle events by looking them up in `self.keymaps`. Returns None
@@ -126,6 +449,16 @@ pub struct TextWithStyle {
 }
 
 impl TextWithStyle {
+    pub fn new(
+        text: impl Into<SharedString>,
+        highlights: Vec<(std::ops::Range<usize>, HighlightStyle)>,
+    ) -> Self {
+        TextWithStyle {
+            text: text.into(),
+            highlights,
+        }
+    }
+
     pub fn from_buffer(buf: Buffer) -> Self {
         let mut highlights: Vec<(std::ops::Range<usize>, HighlightStyle)> = Vec::new();
 
@@ -136,13 +469,7 @@ impl TextWithStyle {
             let mut line = String::new();
             for x in 0..rect.width {
                 let cell = &buf[(x, y)];
-                let bg = crate::utils::color_to_hsla(cell.bg);
-                let fg = crate::utils::color_to_hsla(cell.fg);
-                let new_style = HighlightStyle {
-                    color: fg,
-                    background_color: bg,
-                    ..Default::default()
-                };
+                let new_style = style_to_highlight(cell.style());
                 let length = cell.symbol.len();
                 let new_range = if let Some((range, current_highlight)) = highlights.last_mut() {
                     if &new_style == current_highlight {
@@ -218,3 +545,85 @@ pub fn load_tutor(editor: &mut helix_view::editor::Editor) -> Result<(), anyhow:
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CyrillicLayout;
+    use helix_view::input::KeyEvent;
+    use helix_view::keyboard::{KeyCode, KeyModifiers};
+
+    fn physical_char(c: char, layout: CyrillicLayout) -> char {
+        let key = KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::NONE,
+        };
+        match physical_key(key, layout).code {
+            KeyCode::Char(c) => c,
+            code => panic!("unexpected key code {:?}", code),
+        }
+    }
+
+    #[test]
+    fn qwerty_char_letters() {
+        assert_eq!(qwerty_char('й', CyrillicLayout::Ukrainian), Some('q'));
+        assert_eq!(qwerty_char('ф', CyrillicLayout::Ukrainian), Some('a'));
+        assert_eq!(qwerty_char('ь', CyrillicLayout::Ukrainian), Some('m'));
+        assert_eq!(qwerty_char('ў', CyrillicLayout::Ukrainian), Some('o'));
+        assert_eq!(qwerty_char('і', CyrillicLayout::Ukrainian), Some('s'));
+        assert_eq!(qwerty_char('ς', CyrillicLayout::Ukrainian), Some('w'));
+        assert_eq!(qwerty_char('ω', CyrillicLayout::Ukrainian), Some('v'));
+        assert_eq!(qwerty_char('q', CyrillicLayout::Ukrainian), None);
+        assert_eq!(qwerty_char('1', CyrillicLayout::Ukrainian), None);
+    }
+
+    #[test]
+    fn qwerty_char_punctuation() {
+        assert_eq!(qwerty_char('х', CyrillicLayout::Ukrainian), Some('['));
+        assert_eq!(qwerty_char('ї', CyrillicLayout::Ukrainian), Some(']'));
+        assert_eq!(qwerty_char('ж', CyrillicLayout::Ukrainian), Some(';'));
+        assert_eq!(qwerty_char('є', CyrillicLayout::Ukrainian), Some('\''));
+        assert_eq!(qwerty_char('б', CyrillicLayout::Ukrainian), Some(','));
+        assert_eq!(qwerty_char('ю', CyrillicLayout::Ukrainian), Some('.'));
+        assert_eq!(qwerty_char('ґ', CyrillicLayout::Ukrainian), Some('`'));
+    }
+
+    #[test]
+    fn qwerty_char_belarusian() {
+        let layout = CyrillicLayout::Belarusian;
+        assert_eq!(qwerty_char('і', layout), Some('b'));
+        assert_eq!(qwerty_char('ы', layout), Some('s'));
+        assert_eq!(qwerty_char('ў', layout), Some('o'));
+        assert_eq!(qwerty_char('э', layout), Some('\''));
+        assert_eq!(qwerty_char('ё', layout), Some('`'));
+        assert_eq!(qwerty_char('й', layout), Some('q'));
+        assert_eq!(physical_char('І', layout), 'B');
+        assert_eq!(physical_char('Ў', layout), 'O');
+        assert_eq!(physical_char('Э', layout), '"');
+    }
+
+    #[test]
+    fn physical_key_shifted() {
+        assert_eq!(physical_char('Й', CyrillicLayout::Ukrainian), 'Q');
+        assert_eq!(physical_char('Σ', CyrillicLayout::Ukrainian), 'S');
+        assert_eq!(physical_char('Х', CyrillicLayout::Ukrainian), '{');
+        assert_eq!(physical_char('Ъ', CyrillicLayout::Ukrainian), '}');
+        assert_eq!(physical_char('Ж', CyrillicLayout::Ukrainian), ':');
+        assert_eq!(physical_char('Э', CyrillicLayout::Ukrainian), '"');
+        assert_eq!(physical_char('Б', CyrillicLayout::Ukrainian), '<');
+        assert_eq!(physical_char('Ю', CyrillicLayout::Ukrainian), '>');
+        assert_eq!(physical_char('Ё', CyrillicLayout::Ukrainian), '~');
+    }
+
+    #[test]
+    fn physical_key_keeps_other_keys() {
+        assert_eq!(physical_char('x', CyrillicLayout::Ukrainian), 'x');
+        assert_eq!(physical_char('X', CyrillicLayout::Ukrainian), 'X');
+        assert_eq!(physical_char('ä', CyrillicLayout::Ukrainian), 'ä');
+        let key = KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        };
+        assert_eq!(physical_key(key, CyrillicLayout::Ukrainian), key);
+    }
+}
@@ -2,6 +2,8 @@ use std::collections::{HashMap, HashSet};
 
 use gpui::prelude::FluentBuilder;
 use gpui::*;
+use helix_view::graphics::Rect;
+use helix_view::tree::{ContainerItem, Layout, Tree};
 use helix_view::ViewId;
 use log::info;
 
@@ -12,6 +14,72 @@ use crate::overlay::OverlayView;
 use crate::utils;
 use crate::{Core, Input, InputEvent};
 
+pub const TOP_BAR_HEIGHT: Pixels = px(32.);
+/// Thickness of the grabbable area between two splits.
+const DIVIDER_SIZE: Pixels = px(5.);
+/// Smallest share of its container a split can be resized to.
+const MIN_SPLIT_WEIGHT: f32 = 0.05;
+/// Share of its container a split grows or shrinks by with the split actions.
+const SPLIT_WEIGHT_STEP: f32 = 0.05;
+/// Smallest and largest number of zoom steps, half and three times the configured sizes.
+const MIN_ZOOM_STEPS: i32 = -5;
+const MAX_ZOOM_STEPS: i32 = 20;
+
+/// Split containers of helix's view tree, with their children in order.
+struct Splits {
+    root: Option<ViewId>,
+    layouts: HashMap<ViewId, Layout>,
+    children: HashMap<ViewId, Vec<ViewId>>,
+}
+
+impl Splits {
+    fn new(tree: &Tree) -> Self {
+        let mut splits = Splits {
+            root: None,
+            layouts: HashMap::new(),
+            children: HashMap::new(),
+        };
+        for item in tree.traverse_containers() {
+            match item {
+                ContainerItem::Container { id, parent, layout } => {
+                    splits.layouts.insert(id, layout);
+                    if id == parent {
+                        splits.root = Some(id);
+                    } else {
+                        splits.children.entry(parent).or_default().push(id);
+                    }
+                }
+                ContainerItem::Child { id, parent } => {
+                    splits.children.entry(parent).or_default().push(id);
+                }
+            }
+        }
+        splits
+    }
+
+    /// Container of `id` and the index of `id` among its children.
+    fn position(&self, id: ViewId) -> Option<(ViewId, usize)> {
+        self.children.iter().find_map(|(container, children)| {
+            let idx = children.iter().position(|child| *child == id)?;
+            Some((*container, idx))
+        })
+    }
+}
+
+/// Divider being dragged, between child `index` and the next one.
+#[derive(Debug, Clone, Copy)]
+struct DraggedDivider {
+    container: ViewId,
+    index: usize,
+}
+
+impl Render for DraggedDivider {
+    fn render(&mut self, _cx: &mut ViewContext<Self>) -> impl IntoElement {
+        // the divider itself follows the mouse, nothing to draw
+        div()
+    }
+}
+
 pub struct Workspace {
     core: Model<Core>,
     input: Model<Input>,
@@ -22,6 +90,12 @@ pub struct Workspace {
     info: View<InfoBoxView>,
     info_hidden: bool,
     notifications: View<NotificationView>,
+    /// Relative sizes of the children of each split container.
+    /// helix's tree only knows equal splits, views are moved to where
+    /// the weights put them and get their size from the GUI layout.
+    split_weights: HashMap<ViewId, Vec<f32>>,
+    /// Area the editor and compositor were last resized to.
+    tree_area: Option<Rect>,
 }
 
 impl Workspace {
@@ -34,7 +108,7 @@ impl Workspace {
         let notifications = Self::init_notifications(&core, cx);
         let info = Self::init_info_box(&core, cx);
         let overlay = cx.new_view(|cx| {
-            let view = OverlayView::new(&cx.focus_handle());
+            let view = OverlayView::new(core.clone(), input.clone(), &cx.focus_handle());
             view.subscribe(&core, cx);
             view
         });
@@ -49,6 +123,8 @@ impl Workspace {
             info_hidden: true,
             documents: HashMap::default(),
             notifications,
+            split_weights: HashMap::default(),
+            tree_area: None,
         }
     }
 
@@ -124,7 +200,7 @@ impl Workspace {
                 }
                 cx.notify();
             }
-            crate::Update::Prompt(_) | crate::Update::Picker(_) => {
+            crate::Update::Overlay(_) => {
                 // handled by overlay
                 cx.notify();
             }
@@ -132,39 +208,290 @@ impl Workspace {
                 self.info_hidden = false;
                 // handled by the info box view
             }
+            crate::Update::TextZoom(zoom) => self.zoom(*zoom, cx),
+        }
+    }
+
+    /// Rescales all text, views get their new rows and columns on the next render.
+    fn zoom(&mut self, zoom: crate::TextZoom, cx: &mut ViewContext<Self>) {
+        use crate::TextZoom;
+
+        let current = cx.global::<crate::FontSettings>().zoom_steps;
+        let steps = match zoom {
+            TextZoom::In => current + 1,
+            TextZoom::Out => current - 1,
+            TextZoom::Reset => 0,
+        }
+        .clamp(MIN_ZOOM_STEPS, MAX_ZOOM_STEPS);
+
+        let config = cx.global::<crate::config::GuiConfig>();
+        if config.remember_zoom {
+            crate::config::save_zoom(steps);
+        }
+        let font_settings = crate::FontSettings::new(config, steps);
+        cx.set_global(font_settings);
+        cx.refresh();
+    }
+
+    /// Relative sizes of the children of `container`, reset to equal
+    /// sizes when children were added or removed.
+    fn split_weights(&mut self, container: ViewId, count: usize) -> &mut Vec<f32> {
+        let weights = self.split_weights.entry(container).or_default();
+        if weights.len() != count {
+            *weights = vec![1. / count as f32; count];
         }
+        weights
+    }
+
+    /// Moves the divider after child `index` of `container` to `fraction`
+    /// of the container's size.
+    fn move_divider(&mut self, container: ViewId, count: usize, index: usize, fraction: f32) {
+        let weights = self.split_weights(container, count);
+        if index + 1 >= weights.len() {
+            return;
+        }
+        let before: f32 = weights[..index].iter().sum();
+        let pair = weights[index] + weights[index + 1];
+        let weight = (fraction - before).clamp(MIN_SPLIT_WEIGHT, pair - MIN_SPLIT_WEIGHT);
+        weights[index] = weight;
+        weights[index + 1] = pair - weight;
+    }
+
+    /// Grows the focused view by `delta` of its container, taking the space
+    /// from the next sibling (or the previous one for the last child).
+    fn resize_focused_split(&mut self, delta: f32, cx: &mut ViewContext<Self>) {
+        let Some(view_id) = self.focused_view_id else {
+            return;
+        };
+        let splits = Splits::new(&self.core.read(cx).editor.tree);
+        let Some((container, index)) = splits.position(view_id) else {
+            return;
+        };
+        let count = splits.children[&container].len();
+        if count < 2 {
+            return;
+        }
+        let neighbour = if index + 1 < count {
+            index + 1
+        } else {
+            index - 1
+        };
+        let weights = self.split_weights(container, count);
+        let delta = delta
+            .min(weights[neighbour] - MIN_SPLIT_WEIGHT)
+            .max(MIN_SPLIT_WEIGHT - weights[index]);
+        weights[index] += delta;
+        weights[neighbour] -= delta;
+        cx.notify();
+    }
+
+    /// Origins of the views inside `area` once the container `id` is split
+    /// by its weights, in the cells of helix's tree.
+    fn place_views(
+        &mut self,
+        id: ViewId,
+        area: Rect,
+        splits: &Splits,
+        origins: &mut Vec<(ViewId, u16, u16)>,
+    ) {
+        let Some(layout) = splits.layouts.get(&id).copied() else {
+            origins.push((id, area.x, area.y));
+            return;
+        };
+        let children = splits.children.get(&id).cloned().unwrap_or_default();
+        let weights = self.split_weights(id, children.len()).clone();
+        let length = match layout {
+            Layout::Horizontal => area.height,
+            Layout::Vertical => area.width,
+        };
+        let mut before = 0.;
+        for (child_id, weight) in children.into_iter().zip(weights) {
+            let start = (before * length as f32).round() as u16;
+            before += weight;
+            let end = (before * length as f32).round().min(length as f32) as u16;
+            let child_area = match layout {
+                Layout::Horizontal => Rect::new(
+                    area.x,
+                    area.y + start,
+                    area.width,
+                    end.saturating_sub(start),
+                ),
+                Layout::Vertical => Rect::new(
+                    area.x + start,
+                    area.y,
+                    end.saturating_sub(start),
+                    area.height,
+                ),
+            };
+            self.place_views(child_id, child_area, splits, origins);
+        }
+    }
+
+    fn equalize_splits(&mut self, cx: &mut ViewContext<Self>) {
+        self.split_weights.clear();
+        cx.notify();
     }
 
     fn render_tree(
-        root_id: ViewId,
-        root: Div,
-        containers: &mut HashMap<ViewId, Div>,
-        tree: &HashMap<ViewId, Vec<ViewId>>,
+        &mut self,
+        id: ViewId,
+        splits: &Splits,
+        divider_color: Hsla,
+        dividers: &mut usize,
+        cx: &mut ViewContext<Self>,
     ) -> Div {
-        let mut root = root;
-        if let Some(children) = tree.get(&root_id) {
-            for child_id in children {
-                let child = containers.remove(child_id).unwrap();
-                let child = Self::render_tree(*child_id, child, containers, tree);
-                root = root.child(child);
+        let Some(layout) = splits.layouts.get(&id).copied() else {
+            let view = self.documents.get(&id).unwrap().clone();
+            return div().flex().size_full().child(view);
+        };
+        let children = splits.children.get(&id).cloned().unwrap_or_default();
+        let count = children.len();
+        let weights = self.split_weights(id, count).clone();
+
+        let mut container = match layout {
+            Layout::Horizontal => div().flex().size_full().flex_col(),
+            Layout::Vertical => div().flex().size_full().flex_row(),
+        }
+        .on_drag_move(
+            cx.listener(move |this, ev: &DragMoveEvent<DraggedDivider>, cx| {
+                let divider = *ev.drag(cx);
+                if divider.container != id {
+                    return;
+                }
+                let (bounds, position) = (ev.bounds, ev.event.position);
+                let fraction = match layout {
+                    Layout::Horizontal => (position.y - bounds.top()) / bounds.size.height,
+                    Layout::Vertical => (position.x - bounds.left()) / bounds.size.width,
+                };
+                this.move_divider(id, count, divider.index, fraction);
+                cx.notify();
+            }),
+        );
+
+        for (idx, (child_id, weight)) in children.into_iter().zip(weights).enumerate() {
+            if idx > 0 {
+                let divider = DraggedDivider {
+                    container: id,
+                    index: idx - 1,
+                };
+                container = container.child(Self::render_divider(
+                    divider,
+                    layout,
+                    divider_color,
+                    *dividers,
+                    cx,
+                ));
+                *dividers += 1;
             }
+            let child = self.render_tree(child_id, splits, divider_color, dividers, cx);
+            container = container.child(
+                div()
+                    .flex()
+                    .flex_basis(relative(weight))
+                    .flex_shrink()
+                    .min_w(px(0.))
+                    .min_h(px(0.))
+                    .overflow_hidden()
+                    .child(child),
+            );
         }
-        root
+        container
+    }
+
+    fn render_divider(
+        divider: DraggedDivider,
+        layout: Layout,
+        color: Hsla,
+        idx: usize,
+        cx: &mut ViewContext<Self>,
+    ) -> Stateful<Div> {
+        // the line is thin, the handle around it is easier to grab
+        let handle = match layout {
+            Layout::Horizontal => div()
+                .w_full()
+                .h(DIVIDER_SIZE)
+                .cursor_row_resize()
+                .child(div().w_full().h(px(1.)).bg(color)),
+            Layout::Vertical => div()
+                .h_full()
+                .w(DIVIDER_SIZE)
+                .cursor_col_resize()
+                .child(div().h_full().w(px(1.)).bg(color)),
+        };
+        let container = divider.container;
+        handle
+            .flex()
+            .flex_none()
+            .justify_center()
+            .items_center()
+            .id(("split-divider", idx))
+            .on_drag(divider, |divider, cx| cx.new_view(|_| *divider))
+            .on_click(cx.listener(move |this, ev: &ClickEvent, cx| {
+                if ev.up.click_count == 2 {
+                    this.split_weights.remove(&container);
+                    cx.notify();
+                }
+            }))
     }
 
     fn handle_key(&mut self, ev: &KeyDownEvent, cx: &mut ViewContext<Self>) {
         println!("WORKSPACE KEY DOWN: {:?}", ev.keystroke);
 
-        let key = utils::translate_key(&ev.keystroke);
+        let Some(mut key) = utils::translate_key(&ev.keystroke) else {
+            return;
+        };
+        let config = cx.global::<crate::config::GuiConfig>();
+        if config.physical_keys {
+            let layout = config.cyrillic_layout;
+            use helix_view::document::Mode;
+
+            // text goes to insert mode, prompts, pickers and the completion menu
+            let is_typing = self.core.update(cx, |core, _cx| {
+                let compositor = &mut core.compositor;
+                core.editor.mode() == Mode::Insert
+                    || compositor.find::<helix_term::ui::Prompt>().is_some()
+                    || compositor
+                        .find::<crate::commands::CommandPrompt>()
+                        .is_some()
+                    || compositor
+                        .find_id::<crate::picker::Picker>(crate::picker::Picker::ID)
+                        .is_some()
+                    || compositor
+                        .find_id::<crate::completion::Completion>(crate::completion::Completion::ID)
+                        .is_some()
+            });
+            if !is_typing {
+                key = utils::physical_key(key, layout);
+            }
+        }
         self.input.update(cx, |_, cx| {
             cx.emit(InputEvent::Key(key));
-        })
+        });
+        // handled, the platform must not type it again through the document's input handler
+        cx.stop_propagation();
+    }
+
+    fn document_style(cx: &mut ViewContext<Self>) -> TextStyle {
+        cx.global::<crate::FontSettings>().document_style()
+    }
+
+    /// Area of the view tree in cells, the window below the top bar and inside the padding.
+    /// Views get their exact size when their document is laid out.
+    fn tree_area(cx: &mut ViewContext<Self>) -> Rect {
+        let style = Self::document_style(cx);
+        let cell_width = utils::cell_width(&style, cx);
+        let line_height = style.line_height_in_pixels(cx.rem_size());
+
+        let padding = cx.global::<crate::FontSettings>().padding;
+        let size = cx.viewport_size();
+        let columns = ((size.width - padding * 2.) / cell_width).floor() as u16;
+        let rows = ((size.height - TOP_BAR_HEIGHT - padding * 2.) / line_height).floor() as u16;
+        Rect::new(0, 0, columns, rows)
     }
 
     fn make_views(
         &mut self,
         view_ids: &mut HashSet<ViewId>,
-        right_borders: &mut HashSet<ViewId>,
         cx: &mut ViewContext<Self>,
     ) -> Option<String> {
         let editor = &self.core.read(cx).editor;
@@ -173,14 +500,6 @@ impl Workspace {
         for (view, is_focused) in editor.tree.views() {
             let view_id = view.id;
 
-            if editor
-                .tree
-                .find_split_in_direction(view_id, helix_view::tree::Direction::Right)
-                .is_some()
-            {
-                right_borders.insert(view_id);
-            }
-
             view_ids.insert(view_id);
 
             if is_focused {
@@ -193,11 +512,7 @@ impl Workspace {
         for view_id in view_ids.iter() {
             let view_id = *view_id;
             let is_focused = self.focused_view_id == Some(view_id);
-            let style = TextStyle {
-                font_family: cx.global::<crate::FontSettings>().fixed_font.family.clone(),
-                font_size: px(14.0).into(),
-                ..Default::default()
-            };
+            let style = Self::document_style(cx);
             let core = self.core.clone();
             let input = self.input.clone();
             let view = self.documents.entry(view_id).or_insert_with(|| {
@@ -214,6 +529,7 @@ impl Workspace {
             });
             view.update(cx, |view, _cx| {
                 view.set_focused(is_focused);
+                view.set_style(style);
             });
         }
         focused_file_name
@@ -223,9 +539,8 @@ impl Workspace {
 impl Render for Workspace {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
         let mut view_ids = HashSet::new();
-        let mut right_borders = HashSet::new();
 
-        let focused_file_name = self.make_views(&mut view_ids, &mut right_borders, cx);
+        let focused_file_name = self.make_views(&mut view_ids, cx);
 
         let editor = &self.core.read(cx).editor;
 
@@ -236,47 +551,9 @@ impl Render for Workspace {
         let window_style = editor.theme.get("ui.window");
         let border_color = utils::color_to_hsla(window_style.fg.unwrap()).unwrap_or(white());
 
-        let editor_rect = editor.tree.area();
-
-        use helix_view::tree::{ContainerItem, Layout};
-        let mut containers = HashMap::new();
-        let mut tree = HashMap::new();
-        let mut root_id = None;
-
-        let editor = &self.core.read(cx).editor;
-        for item in editor.tree.traverse_containers() {
-            match item {
-                ContainerItem::Container { id, parent, layout } => {
-                    let container = match layout {
-                        Layout::Horizontal => div().flex().size_full().flex_col(),
-                        Layout::Vertical => div().flex().size_full().flex_row(),
-                    };
-                    containers.insert(id, container);
-                    let entry = tree.entry(parent).or_insert_with(|| Vec::new());
-
-                    if id == parent {
-                        root_id = Some(id);
-                    } else {
-                        entry.push(id);
-                    }
-                }
-                ContainerItem::Child { id, parent } => {
-                    let view = self.documents.get(&id).unwrap().clone();
-                    let has_border = right_borders.contains(&id);
-                    let view = div()
-                        .flex()
-                        .size_full()
-                        .child(view)
-                        .when(has_border, |this| {
-                            this.border_color(border_color).border_r_1()
-                        });
-
-                    let mut container = containers.remove(&parent).unwrap();
-                    container = container.child(view);
-                    containers.insert(parent, container);
-                }
-            }
-        }
+        let splits = Splits::new(&editor.tree);
+        self.split_weights
+            .retain(|id, _| splits.layouts.contains_key(id));
 
         let to_remove = self
             .documents
@@ -291,10 +568,8 @@ impl Render for Workspace {
         }
 
         let mut docs_root = None;
-        // println!("containers: {:?} tree: {:?}", containers.len(), tree);
-        if let Some(root_id) = root_id {
-            let root = containers.remove(&root_id).unwrap();
-            let child = Self::render_tree(root_id, root, &mut containers, &tree);
+        if let Some(root_id) = splits.root {
+            let child = self.render_tree(root_id, &splits, border_color, &mut 0, cx);
             let root = div().flex().w_full().h_full().child(child);
             docs_root = Some(root);
         }
@@ -303,9 +578,11 @@ impl Render for Workspace {
         //     docs.push(AnyView::from(view.clone()).cached(StyleRefinement::default().size_full()));
         // }
 
+        // the overlay takes focus when it shows up, keep it there while it's open
         let focused_view = self
             .focused_view_id
             .and_then(|id| self.documents.get(&id))
+            .filter(|_| self.overlay.read(cx).is_empty())
             .cloned();
         if let Some(view) = &focused_view {
             cx.focus_view(view);
@@ -316,7 +593,7 @@ impl Render for Workspace {
                 .flex_shrink()
                 .font(cx.global::<crate::FontSettings>().var_font.clone())
                 .text_color(text_color)
-                .text_size(px(12.))
+                .text_size(cx.global::<crate::FontSettings>().ui_font_size)
                 .child(format!("{} - Helix", path))
         } else {
             div().flex()
@@ -325,16 +602,37 @@ impl Render for Workspace {
             .w_full()
             .flex()
             .flex_none()
-            .h_8()
+            .h(TOP_BAR_HEIGHT)
             .justify_center()
             .items_center()
             .child(label);
 
         println!("rendering workspace");
 
-        self.core.update(cx, |core, _cx| {
-            core.compositor.resize(editor_rect);
-        });
+        // resizing lays helix's tree out again, only do it when the window changed
+        let tree_area = Self::tree_area(cx);
+        if self.tree_area != Some(tree_area) {
+            self.tree_area = Some(tree_area);
+            self.core.update(cx, |core, _cx| {
+                core.editor.resize(tree_area);
+                core.compositor.resize(tree_area);
+            });
+        }
+
+        // helix navigates between views and maps the mouse by their areas
+        if let (Some(root_id), Some(tree_area)) = (splits.root, self.tree_area) {
+            let mut origins = Vec::new();
+            self.place_views(root_id, tree_area, &splits, &mut origins);
+            self.core.update(cx, |core, _cx| {
+                for (view_id, x, y) in origins {
+                    if core.editor.tree.contains(view_id) {
+                        let area = &mut core.editor.tree.get_mut(view_id).area;
+                        area.x = x;
+                        area.y = y;
+                    }
+                }
+            });
+        }
 
         if let Some(view) = &focused_view {
             cx.focus_view(view);
@@ -372,6 +670,18 @@ impl Render for Workspace {
             .on_action(move |&crate::ShowAll, cx| cx.unhide_other_apps())
             .on_action(move |&crate::Minimize, cx| cx.minimize_window())
             .on_action(move |&crate::Zoom, cx| cx.zoom_window())
+            .on_action(cx.listener(|this, &crate::GrowSplit, cx| {
+                this.resize_focused_split(SPLIT_WEIGHT_STEP, cx)
+            }))
+            .on_action(cx.listener(|this, &crate::ShrinkSplit, cx| {
+                this.resize_focused_split(-SPLIT_WEIGHT_STEP, cx)
+            }))
+            .on_action(cx.listener(|this, &crate::EqualizeSplits, cx| this.equalize_splits(cx)))
+            .on_action(cx.listener(|this, &crate::ZoomIn, cx| this.zoom(crate::TextZoom::In, cx)))
+            .on_action(cx.listener(|this, &crate::ZoomOut, cx| this.zoom(crate::TextZoom::Out, cx)))
+            .on_action(
+                cx.listener(|this, &crate::ResetZoom, cx| this.zoom(crate::TextZoom::Reset, cx)),
+            )
             .on_action({
                 let handle = self.handle.clone();
                 let core = self.core.clone();
@@ -385,14 +695,13 @@ impl Render for Workspace {
             .flex_col()
             .w_full()
             .h_full()
+            .p(cx.global::<crate::FontSettings>().padding)
             .focusable()
             .child(top_bar)
             .when_some(docs_root, |this, docs| this.child(docs))
             .child(self.notifications.clone())
             .when(!self.overlay.read(cx).is_empty(), |this| {
-                let view = &self.overlay;
-                cx.focus_view(&view);
-                this.child(view.clone())
+                this.child(self.overlay.clone())
             })
             .when(
                 !self.info_hidden && !self.info.read(cx).is_empty(),